@@ -0,0 +1,34 @@
+use actix_web::{get, web::Json};
+use serde::Serialize;
+
+use crate::INSTANCES_RECORD;
+
+use super::Admin;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AdminInstance {
+    pub address: String,
+    pub region: String,
+    pub bucket: &'static str,
+}
+
+#[get("/instances")]
+pub async fn instances(_: Admin) -> Json<Vec<AdminInstance>> {
+    let records = INSTANCES_RECORD.get().unwrap().lock().unwrap();
+    let mut out = Vec::new();
+
+    for (region, records) in records.0.iter() {
+        for address in records.all_instances() {
+            out.push(AdminInstance {
+                address: address.to_string(),
+                region: region.to_string(),
+                bucket: records.bucket(address).unwrap(),
+            });
+        }
+    }
+
+    out.sort_by(|a, b| a.address.cmp(&b.address));
+
+    Json(out)
+}
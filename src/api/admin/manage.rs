@@ -0,0 +1,124 @@
+use actix_web::{
+    post,
+    web::{Json, Query},
+};
+use log::info;
+use serde::Deserialize;
+
+use crate::{remove_instances, RegionRecords, INSTANCES_RECORD, OUTBOUND_CONFIG};
+
+use super::{instances::AdminInstance, persist, Admin, AdminResponse, InstanceQuery};
+
+/// Applies `action` to the region holding the instance, then saves.
+async fn apply(
+    instance: &str,
+    name: &str,
+    action: impl FnOnce(&mut RegionRecords, &str) -> bool,
+) -> Json<AdminResponse<AdminInstance>> {
+    let result = {
+        let mut records = INSTANCES_RECORD.get().unwrap().lock().unwrap();
+        match records.region_of_mut(instance) {
+            Some(found) => {
+                if action(found, instance) {
+                    Ok(AdminInstance {
+                        address: instance.to_string(),
+                        region: found
+                            .all_records()
+                            .into_iter()
+                            .find(|record| record.address == instance)
+                            .map(|record| record.region.clone())
+                            .unwrap_or_default(),
+                        bucket: found.bucket(instance).unwrap(),
+                    })
+                } else {
+                    Err("instance already in target state")
+                }
+            }
+            None => Err("no such instance"),
+        }
+    };
+
+    match result {
+        Ok(instance) => {
+            info!("Admin: {name} {}", instance.address);
+            persist().await;
+            Json(AdminResponse::Success(instance))
+        }
+        Err(e) => Json(AdminResponse::error(e)),
+    }
+}
+
+#[post("/kill")]
+pub async fn kill(_: Admin, query: Query<InstanceQuery>) -> Json<AdminResponse<AdminInstance>> {
+    apply(&query.instance, "kill", RegionRecords::kill).await
+}
+
+#[post("/rest")]
+pub async fn rest(_: Admin, query: Query<InstanceQuery>) -> Json<AdminResponse<AdminInstance>> {
+    apply(&query.instance, "rest", RegionRecords::rest).await
+}
+
+#[post("/revive")]
+pub async fn revive(_: Admin, query: Query<InstanceQuery>) -> Json<AdminResponse<AdminInstance>> {
+    apply(&query.instance, "revive", RegionRecords::revive).await
+}
+
+#[post("/reset-weight")]
+pub async fn reset_weight(
+    _: Admin,
+    query: Query<InstanceQuery>,
+) -> Json<AdminResponse<AdminInstance>> {
+    apply(
+        &query.instance,
+        "reset weight of",
+        RegionRecords::reset_weight,
+    )
+    .await
+}
+
+#[post("/delete")]
+pub async fn delete(_: Admin, query: Query<InstanceQuery>) -> Json<AdminResponse<String>> {
+    if !remove_instances(std::slice::from_ref(&query.instance)).await {
+        return Json(AdminResponse::error("no such instance"));
+    }
+
+    info!("Admin: delete {}", query.instance);
+
+    Json(AdminResponse::Success(query.instance.clone()))
+}
+
+#[derive(Deserialize)]
+struct RegionQuery {
+    pub instance: String,
+    pub region: String,
+}
+
+#[post("/region")]
+pub async fn region(_: Admin, query: Query<RegionQuery>) -> Json<AdminResponse<AdminInstance>> {
+    if !OUTBOUND_CONFIG
+        .get()
+        .unwrap()
+        .offsets
+        .0
+        .contains_key(&query.region)
+    {
+        return Json(AdminResponse::error("no such region"));
+    }
+
+    let bucket = {
+        let mut records = INSTANCES_RECORD.get().unwrap().lock().unwrap();
+        if !records.set_region(&query.instance, &query.region) {
+            return Json(AdminResponse::error("no such instance"));
+        }
+        records.0[&query.region].bucket(&query.instance).unwrap()
+    };
+
+    info!("Admin: move {} to {}", query.instance, query.region);
+    persist().await;
+
+    Json(AdminResponse::Success(AdminInstance {
+        address: query.instance.clone(),
+        region: query.region.clone(),
+        bucket,
+    }))
+}
@@ -0,0 +1,81 @@
+use std::future::{ready, Ready};
+
+use actix_web::{
+    dev::Payload, error::ErrorUnauthorized, http::header::AUTHORIZATION, FromRequest, HttpRequest,
+    Scope,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{ADMIN_CONFIG, INSTANCES_RECORD, INSTANCES_STATS, STORAGE};
+
+mod instances;
+mod manage;
+mod poll;
+mod reload;
+
+pub fn scope() -> Scope {
+    Scope::new("/admin")
+        .service(instances::instances)
+        .service(manage::kill)
+        .service(manage::rest)
+        .service(manage::revive)
+        .service(manage::delete)
+        .service(manage::reset_weight)
+        .service(manage::region)
+        .service(poll::poll)
+        .service(reload::reload)
+}
+
+/// Extracting this fails unless the request has `Authorization: Bearer <token>` matching
+/// `admin.json`.
+pub struct Admin;
+
+impl FromRequest for Admin {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let token = req
+            .headers()
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+
+        let authorised = match (ADMIN_CONFIG.get().unwrap(), token) {
+            (Some(config), Some(token)) => config.authorised(token),
+            _ => false,
+        };
+
+        ready(if authorised {
+            Ok(Admin)
+        } else {
+            Err(ErrorUnauthorized("Unauthorized"))
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct InstanceQuery {
+    pub instance: String,
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum AdminResponse<T: Serialize> {
+    Success(T),
+    Error { error: String },
+}
+
+impl<T: Serialize> AdminResponse<T> {
+    fn error(error: &str) -> Self {
+        Self::Error {
+            error: error.to_string(),
+        }
+    }
+}
+
+async fn persist() {
+    let stats = INSTANCES_RECORD.get().unwrap().lock().unwrap().stat();
+    *INSTANCES_STATS.get().unwrap().lock().unwrap() = stats;
+    let _ = STORAGE.get().unwrap().save_instances().await;
+}
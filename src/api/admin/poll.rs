@@ -0,0 +1,43 @@
+use actix_web::{
+    post,
+    web::{Json, Query},
+};
+use log::info;
+
+use crate::{PolledSingleRecord, PollingRecord, INSTANCES_RECORD, STORAGE};
+
+use super::{persist, Admin, AdminResponse, InstanceQuery};
+
+#[post("/poll")]
+pub async fn poll(
+    _: Admin,
+    query: Query<InstanceQuery>,
+) -> Json<AdminResponse<PolledSingleRecord>> {
+    if !INSTANCES_RECORD
+        .get()
+        .unwrap()
+        .lock()
+        .unwrap()
+        .0
+        .values()
+        .any(|region| region.contains(&query.instance))
+    {
+        return Json(AdminResponse::error("no such instance"));
+    }
+
+    info!("Admin: poll {}", query.instance);
+
+    match PollingRecord::poll_single(query.instance.clone()).await {
+        Some(record) => {
+            persist().await;
+            let _ = STORAGE.get().unwrap().save_polling().await;
+            Json(AdminResponse::Success(record))
+        }
+        None => {
+            persist().await;
+            Json(AdminResponse::error(
+                "instance is blacklisted and was removed",
+            ))
+        }
+    }
+}
@@ -0,0 +1,14 @@
+use actix_web::{post, web::Json};
+use log::info;
+
+use super::{Admin, AdminResponse};
+
+#[post("/reload")]
+pub async fn reload(_: Admin) -> Json<AdminResponse<()>> {
+    info!("Admin: reload config");
+
+    match crate::reload().await {
+        Ok(()) => Json(AdminResponse::Success(())),
+        Err(e) => Json(AdminResponse::error(&e)),
+    }
+}
@@ -1,7 +1,10 @@
 use actix_web::Scope;
 
+pub mod admin;
 pub mod v1;
 
 pub fn scope() -> Scope {
-    Scope::new("api").service(v1::scope())
+    Scope::new("api")
+        .service(v1::scope())
+        .service(admin::scope())
 }
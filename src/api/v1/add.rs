@@ -3,9 +3,13 @@ use actix_web::{
     web::{Json, Query},
     HttpRequest,
 };
+use log::info;
 use serde::{Deserialize, Serialize};
 
-use crate::{INSTANCES_RECORD, OUTBOUND_CONFIG};
+use crate::{
+    address, AddressError, Archive, INSTANCES_RECORD, MASTER_CONFIG, METRICS, OUTBOUND_CONFIG,
+    SUBMISSIONS_GLOBAL, SUBMISSIONS_PER_IP,
+};
 
 #[derive(Deserialize)]
 struct AddQuery {
@@ -13,16 +17,60 @@ struct AddQuery {
     pub instance: String,
 }
 
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+enum AddErrorReason {
+    NoSuchRegion,
+    RateLimited,
+    PendingFull,
+    TooManyBacked,
+    #[serde(untagged)]
+    Address(AddressError),
+}
+
 #[derive(Serialize)]
 #[serde(untagged)]
 #[serde(rename_all = "camelCase")]
 enum AddResponse {
-    Success { address: String, region: String },
-    Error { error: String },
+    Success {
+        address: String,
+        region: String,
+        /// Set if the instance was archived or deleted before.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        note: Option<String>,
+    },
+    Error {
+        error: String,
+        reason: AddErrorReason,
+    },
+}
+
+impl AddResponse {
+    fn rejected(ip: &str, reason: AddErrorReason, error: &str) -> Self {
+        info!("Rejected submission from {ip}: {error}");
+        if let Ok(serde_json::Value::String(reason)) = serde_json::to_value(&reason) {
+            METRICS
+                .get()
+                .unwrap()
+                .lock()
+                .unwrap()
+                .submission_rejected(reason);
+        }
+        Self::Error {
+            error: error.to_string(),
+            reason,
+        }
+    }
 }
 
 #[get("/add")]
 async fn add(query: Query<AddQuery>, req: HttpRequest) -> Json<AddResponse> {
+    let ip = req
+        .connection_info()
+        .realip_remote_addr()
+        .unwrap()
+        .to_string();
+
     if !OUTBOUND_CONFIG
         .get()
         .unwrap()
@@ -30,22 +78,88 @@ async fn add(query: Query<AddQuery>, req: HttpRequest) -> Json<AddResponse> {
         .0
         .contains_key(&query.region)
     {
-        return Json(AddResponse::Error {
-            error: "no such region".to_string(),
-        });
+        return Json(AddResponse::rejected(
+            &ip,
+            AddErrorReason::NoSuchRegion,
+            "no such region",
+        ));
+    }
+    let config = &MASTER_CONFIG.get().unwrap().submissions;
+
+    let instance = match address::normalise(&query.instance) {
+        Ok(instance) => instance,
+        Err(e) => {
+            return Json(AddResponse::rejected(
+                &ip,
+                AddErrorReason::Address(e),
+                &e.to_string(),
+            ))
+        }
+    };
+
+    // backing an instance we already know about adds nothing to pending
+    let full = {
+        let records = INSTANCES_RECORD.get().unwrap().lock().unwrap();
+        !records.contains(&instance) && records.pending() >= config.max_pending as usize
+    };
+    if full {
+        return Json(AddResponse::rejected(
+            &ip,
+            AddErrorReason::PendingFull,
+            "too many instances pending, try again later",
+        ));
     }
 
-    INSTANCES_RECORD.get().unwrap().lock().unwrap().add(
-        query.instance.to_string(),
+    if !SUBMISSIONS_PER_IP.get().unwrap().lock().unwrap().hit(&ip)
+        || !SUBMISSIONS_GLOBAL.get().unwrap().lock().unwrap().hit("")
+    {
+        return Json(AddResponse::rejected(
+            &ip,
+            AddErrorReason::RateLimited,
+            "too many submissions, try again later",
+        ));
+    }
+
+    if INSTANCES_RECORD
+        .get()
+        .unwrap()
+        .lock()
+        .unwrap()
+        .backed_by(&ip, &instance)
+        >= config.max_backed_per_ip as usize
+    {
+        return Json(AddResponse::rejected(
+            &ip,
+            AddErrorReason::TooManyBacked,
+            "too many instances submitted from this address",
+        ));
+    }
+
+    if let Err(e) = address::check(&instance).await {
+        return Json(AddResponse::rejected(
+            &ip,
+            AddErrorReason::Address(e),
+            &e.to_string(),
+        ));
+    }
+
+    let added = INSTANCES_RECORD.get().unwrap().lock().unwrap().add(
+        instance.clone(),
         query.region.to_string(),
-        req.connection_info()
-            .realip_remote_addr()
-            .unwrap()
-            .to_string(),
+        ip,
     );
+    METRICS.get().unwrap().lock().unwrap().submission_accepted();
+
+    // only a new record replaces the archived one, backing a listed instance leaves it be
+    let note = if added {
+        Archive::forget(&instance).await
+    } else {
+        None
+    };
 
     Json(AddResponse::Success {
-        address: query.instance.to_string(),
+        address: instance,
         region: query.region.to_string(),
+        note,
     })
 }
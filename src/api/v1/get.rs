@@ -1,12 +1,13 @@
 use actix_web::{
     get,
     web::{Json, Query},
+    HttpRequest,
 };
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    PolledSingleRecord, CHANNEL_ID, INSTANCES_RECORD, OUTBOUND_CONFIG, PLAYLIST_ID, POLLING_RECORD,
-    SEARCH_TERM, VIDEO_ID,
+    client_key, guard, requested_region, shown, PolledOn, PolledSingleRecord, Verdict,
+    INSTANCES_RECORD, OUTBOUND_CONFIG, POLLING_RECORD,
 };
 
 #[derive(Deserialize)]
@@ -21,7 +22,7 @@ enum GetResponse {
     Success {
         address: String,
         offset: i32,
-        polled: PolledSingleRecord,
+        polled: Box<PolledSingleRecord>,
         polled_on: PolledOn,
     },
     Error {
@@ -29,111 +30,63 @@ enum GetResponse {
     },
 }
 
-#[derive(Serialize)]
-pub struct PolledOn {
-    pub video: String,
-    pub playlist: String,
-    pub channel: String,
-    pub search: String,
-}
-
-impl PolledOn {
-    pub fn get() -> Self {
-        Self {
-            video: VIDEO_ID.get().unwrap().lock().unwrap().to_string(),
-            playlist: PLAYLIST_ID.get().unwrap().lock().unwrap().to_string(),
-            channel: CHANNEL_ID.get().unwrap().lock().unwrap().to_string(),
-            search: SEARCH_TERM.get().unwrap().lock().unwrap().to_string(),
-        }
-    }
-}
-
 #[get("/get")]
-pub async fn get(query: Query<GetQuery>) -> Json<GetResponse> {
-    let instance = if let Some(region) = &query.region {
-        let offset = OUTBOUND_CONFIG.get().unwrap().offsets.0.get(region);
-
-        if offset.is_some() {
-            if let Some(region) = INSTANCES_RECORD
-                .get()
-                .unwrap()
-                .lock()
-                .unwrap()
-                .0
-                .get(region)
-            {
-                let hot = &region.hot;
-
-                if hot.is_empty() {
-                    return Json(GetResponse::Error {
-                        error: "no instance".to_string(),
-                    });
-                }
-
-                let sum = hot.iter().map(|entry| entry.weight).sum::<f64>();
-
-                let mut take = fastrand::f64() * sum;
-                let mut instance = hot.len() - 1;
+pub async fn get(query: Query<GetQuery>, req: HttpRequest) -> Json<GetResponse> {
+    let ip = req
+        .connection_info()
+        .realip_remote_addr()
+        .unwrap_or_default()
+        .to_string();
+    let client = client_key(&ip);
+
+    let verdict = guard(&client).await;
+    if let Verdict::Throttle = verdict {
+        return Json(GetResponse::Error {
+            error: "too many requests".to_string(),
+        });
+    }
 
-                for (index, record) in hot.iter().enumerate() {
-                    take -= record.weight;
+    let region = requested_region(query.region.as_deref(), &ip);
+    if region.as_ref().is_some_and(|region| {
+        !OUTBOUND_CONFIG
+            .get()
+            .unwrap()
+            .offsets
+            .0
+            .contains_key(region)
+    }) {
+        return Json(GetResponse::Error {
+            error: "no such region".to_string(),
+        });
+    }
 
-                    if take < 0. {
-                        instance = index;
-                        break;
-                    }
-                }
+    let instance = {
+        let records = INSTANCES_RECORD.get().unwrap().lock().unwrap();
+        let hot = records.candidates(region.as_deref(), query.region.is_none(), |records| {
+            &records.hot
+        });
 
-                hot[instance].instance.clone()
-            } else {
+        match verdict.pick(&hot, &client) {
+            Some(entry) => entry.instance.clone(),
+            None => {
                 return Json(GetResponse::Error {
                     error: "no instance".to_string(),
-                });
-            }
-        } else {
-            return Json(GetResponse::Error {
-                error: "no such region".to_string(),
-            });
-        }
-    } else {
-        let records = INSTANCES_RECORD.get().unwrap().lock().unwrap();
-        let hot = records
-            .0
-            .values()
-            .flat_map(|item| &item.hot)
-            .collect::<Vec<_>>();
-
-        if hot.is_empty() {
-            return Json(GetResponse::Error {
-                error: "no instance".to_string(),
-            });
-        }
-
-        let sum = hot.iter().map(|entry| entry.weight).sum::<f64>();
-
-        let mut take = fastrand::f64() * sum;
-        let mut instance = hot.len() - 1;
-
-        for (index, record) in hot.iter().enumerate() {
-            take -= record.weight;
-
-            if take < 0. {
-                instance = index;
-                break;
+                })
             }
         }
-
-        hot[instance].instance.clone()
     };
 
-    let offset = *OUTBOUND_CONFIG
+    shown(&client, &instance.address);
+
+    let offset = OUTBOUND_CONFIG
         .get()
         .unwrap()
         .offsets
         .0
         .get(&instance.region)
-        .unwrap();
-    let polled = POLLING_RECORD
+        .copied()
+        .unwrap_or_default();
+    let mut polled = POLLING_RECORD
         .get()
         .unwrap()
         .lock()
@@ -143,10 +96,12 @@ pub async fn get(query: Query<GetQuery>) -> Json<GetResponse> {
         .unwrap()
         .clone();
 
+    let polled_on = polled.polled_on.take().unwrap_or_else(PolledOn::get);
+
     Json(GetResponse::Success {
         address: instance.address,
         offset,
-        polled,
-        polled_on: PolledOn::get(),
+        polled: Box::new(polled),
+        polled_on,
     })
 }
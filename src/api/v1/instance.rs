@@ -0,0 +1,24 @@
+use actix_web::{
+    get,
+    web::{Json, Path},
+};
+use serde::Serialize;
+
+use crate::InstanceStatus;
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum InstanceResponse {
+    Success(Box<InstanceStatus>),
+    Error { error: String },
+}
+
+#[get("/instance/{address}")]
+pub async fn instance(address: Path<String>) -> Json<InstanceResponse> {
+    match InstanceStatus::of(&address) {
+        Some(status) => Json(InstanceResponse::Success(Box::new(status))),
+        None => Json(InstanceResponse::Error {
+            error: "no such instance".to_string(),
+        }),
+    }
+}
@@ -1,6 +1,7 @@
 use actix_web::Scope;
 mod add;
 mod get;
+mod instance;
 mod regions;
 mod stats;
 
@@ -10,4 +11,5 @@ pub fn scope() -> Scope {
         .service(regions::regions)
         .service(add::add)
         .service(stats::stats)
+        .service(instance::instance)
 }
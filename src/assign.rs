@@ -0,0 +1,73 @@
+use std::{
+    hash::{DefaultHasher, Hash, Hasher},
+    net::IpAddr,
+    sync::OnceLock,
+};
+
+use chrono::Utc;
+
+use crate::{HotRecord, MASTER_CONFIG};
+
+/// Keeps assignments unpredictable from outside, changes on every restart.
+static SALT: OnceLock<u64> = OnceLock::new();
+
+/// Identifies a client for [`assign`] by its address. IPv6 clients are grouped by their /64,
+/// which usually belongs to a single household, so they cannot get a new assignment by
+/// switching addresses.
+pub fn client_key(ip: &str) -> String {
+    match ip.parse::<IpAddr>() {
+        Ok(IpAddr::V6(ip)) => {
+            let segments = ip.segments();
+            format!(
+                "{:x}:{:x}:{:x}:{:x}::/64",
+                segments[0], segments[1], segments[2], segments[3]
+            )
+        }
+        _ => ip.to_string(),
+    }
+}
+
+/// Picks a hot instance for `client`.
+///
+/// Each client is assigned a small subset of the hot instances by weighted rendezvous
+/// hashing, which stays the same for the whole epoch, and every pick is made from that subset.
+/// Changes to the hot set or weights only move the clients whose instances were affected.
+pub fn assign<'a>(hot: &[&'a HotRecord], client: &str) -> Option<&'a HotRecord> {
+    let config = MASTER_CONFIG.get().unwrap().assignment;
+    let salt = *SALT.get_or_init(|| fastrand::u64(..));
+    let epoch = Utc::now().timestamp() as u64 / config.epoch;
+
+    let mut scored = hot
+        .iter()
+        .map(|record| {
+            let mut hasher = DefaultHasher::new();
+            (salt, client, epoch, &record.instance.address).hash(&mut hasher);
+            // uniform in (0, 1]
+            let unit = ((hasher.finish() >> 11) + 1) as f64 / (1u64 << 53) as f64;
+
+            let score = if record.weight > 0. {
+                -unit.ln() / record.weight
+            } else {
+                f64::INFINITY
+            };
+
+            (score, *record)
+        })
+        .collect::<Vec<_>>();
+
+    scored.sort_by(|a, b| a.0.total_cmp(&b.0));
+    scored.truncate(config.subset);
+
+    let sum = scored.iter().map(|(_, record)| record.weight).sum::<f64>();
+    let mut take = fastrand::f64() * sum;
+
+    for (_, record) in scored.iter() {
+        take -= record.weight;
+
+        if take < 0. {
+            return Some(record);
+        }
+    }
+
+    scored.last().map(|(_, record)| *record)
+}
@@ -0,0 +1,66 @@
+use std::collections::HashMap;
+
+use chrono::Utc;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::{client_key, Directory, SavedFile, BANS};
+
+/// Clients banned automatically for enumerating, by [`client_key`], with the Unix timestamp
+/// each ban expires at.
+///
+/// Kept apart from `blacklists.json`, which is only ever edited by the operator.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct Bans(pub HashMap<String, u64>);
+
+impl SavedFile for Bans {
+    const DIR: Directory = Directory::Data;
+    const PATH: &'static str = "bans.json";
+}
+
+impl Bans {
+    pub fn contains(&self, client: &str) -> bool {
+        let now = Utc::now().timestamp() as u64;
+        self.0.get(client).is_some_and(|expires| *expires > now)
+    }
+
+    /// Bans `client` for `duration` seconds, and saves.
+    pub async fn ban(client: &str, duration: u64) {
+        BANS.get()
+            .unwrap()
+            .lock()
+            .unwrap()
+            .0
+            .insert(client.to_string(), Utc::now().timestamp() as u64 + duration);
+
+        warn!("Banned {client} for {duration}s");
+        let _ = Self::save_latest(|| BANS.get().unwrap().lock().unwrap().clone()).await;
+    }
+
+    /// Drops expired bans, and saves if there were any.
+    pub async fn expire() {
+        let now = Utc::now().timestamp() as u64;
+
+        {
+            let mut bans = BANS.get().unwrap().lock().unwrap();
+            let before = bans.0.len();
+            bans.0.retain(|_, expires| *expires > now);
+
+            if bans.0.len() == before {
+                return;
+            }
+        }
+
+        info!("Expired bans removed");
+        let _ = Self::save_latest(|| BANS.get().unwrap().lock().unwrap().clone()).await;
+    }
+}
+
+/// Whether the client `ip` belongs to is banned.
+pub fn is_banned(ip: &str) -> bool {
+    BANS.get()
+        .unwrap()
+        .lock()
+        .unwrap()
+        .contains(&client_key(ip))
+}
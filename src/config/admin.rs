@@ -0,0 +1,30 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{Directory, SavedFile};
+
+/// The admin API is disabled while the token is empty.
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct AdminConfig {
+    pub token: String,
+}
+
+impl SavedFile for AdminConfig {
+    const DIR: Directory = Directory::Config;
+    const PATH: &'static str = "admin.json";
+}
+
+impl AdminConfig {
+    /// Compares in constant time, so the token cannot be guessed byte by byte.
+    pub fn authorised(&self, token: &str) -> bool {
+        if self.token.is_empty() || self.token.len() != token.len() {
+            return false;
+        }
+
+        self.token
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |acc, (a, b)| acc | (a ^ b))
+            == 0
+    }
+}
@@ -1,13 +1,14 @@
 use std::{
     collections::HashSet,
+    net::IpAddr,
     sync::{Arc, Mutex},
 };
 
 use serde::{Deserialize, Serialize};
 
-use crate::{SavedFile, BLACKLISTED_INSTANCES, BLACKLISTED_IP};
+use crate::{Directory, SavedFile, BLACKLISTED_INSTANCES, BLACKLISTED_IP};
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct BlackLists {
     pub ip: BlackList,
@@ -15,21 +16,30 @@ pub struct BlackLists {
 }
 
 impl SavedFile for BlackLists {
-    const PATH: &'static str = ".config/vidiup/blacklists.json";
+    const DIR: Directory = Directory::Config;
+    const PATH: &'static str = "blacklists.json";
 }
 
 impl BlackLists {
-    pub fn init(&self) {
-        BLACKLISTED_IP
-            .set(Arc::new(Mutex::new(self.ip.hashset())))
-            .unwrap();
-        BLACKLISTED_INSTANCES
-            .set(Arc::new(Mutex::new(self.instance.hashset())))
-            .unwrap();
+    /// Makes these lists the ones in effect, replacing any previously applied.
+    pub fn apply(&self) {
+        match BLACKLISTED_IP.get() {
+            Some(ip) => *ip.lock().unwrap() = self.ip.hashset(),
+            None => {
+                let _ = BLACKLISTED_IP.set(Arc::new(Mutex::new(self.ip.hashset())));
+            }
+        }
+
+        match BLACKLISTED_INSTANCES.get() {
+            Some(instance) => *instance.lock().unwrap() = self.instance.instances(),
+            None => {
+                let _ = BLACKLISTED_INSTANCES.set(Arc::new(Mutex::new(self.instance.instances())));
+            }
+        }
     }
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Default)]
 pub struct BlackList(Vec<String>);
 
 impl BlackList {
@@ -42,4 +52,198 @@ impl BlackList {
 
         out
     }
+
+    pub fn instances(&self) -> InstanceBlackList {
+        let mut out = InstanceBlackList::default();
+
+        for entry in self.0.iter() {
+            let entry = entry.trim().to_lowercase();
+
+            if let Some(range) = IpRange::parse(&entry) {
+                out.ranges.push(range);
+            } else if let Some(domain) = entry.strip_prefix("*.") {
+                out.wildcards.push(domain.to_string());
+            } else {
+                out.hosts.insert(entry);
+            }
+        }
+
+        out
+    }
+}
+
+/// Instance blacklist entries, sorted by how they are matched.
+///
+/// - `example.com` matches the exact host
+/// - `*.example.com` matches any subdomain of `example.com`
+/// - `1.2.3.4` or `1.2.3.0/24` matches hosts resolving into that range
+#[derive(Clone, Default, Debug)]
+pub struct InstanceBlackList {
+    pub hosts: HashSet<String>,
+    pub wildcards: Vec<String>,
+    pub ranges: Vec<IpRange>,
+}
+
+impl InstanceBlackList {
+    /// Checks the host name only, without resolving it.
+    pub fn contains(&self, host: &str) -> bool {
+        let host = host.to_lowercase();
+
+        if self.hosts.contains(&host) {
+            return true;
+        }
+
+        if self.wildcards.iter().any(|domain| {
+            host.strip_suffix(domain.as_str())
+                .is_some_and(|sub| sub.ends_with('.'))
+        }) {
+            return true;
+        }
+
+        host.parse::<IpAddr>().is_ok_and(|ip| self.contains_ip(&ip))
+    }
+
+    pub fn contains_ip(&self, ip: &IpAddr) -> bool {
+        self.ranges.iter().any(|range| range.contains(ip))
+    }
+
+    pub fn contains_any(&self, ips: &[IpAddr]) -> bool {
+        ips.iter().any(|ip| self.contains_ip(ip))
+    }
+}
+
+pub fn is_blacklisted(host: &str) -> bool {
+    BLACKLISTED_INSTANCES
+        .get()
+        .unwrap()
+        .lock()
+        .unwrap()
+        .contains(host)
+}
+
+/// Resolves the host and checks both the name and all of its addresses.
+pub async fn is_blacklisted_resolved(host: &str) -> bool {
+    if is_blacklisted(host) {
+        return true;
+    }
+
+    let ips = resolve(host).await;
+
+    BLACKLISTED_INSTANCES
+        .get()
+        .unwrap()
+        .lock()
+        .unwrap()
+        .contains_any(&ips)
+}
+
+pub async fn resolve(host: &str) -> Vec<IpAddr> {
+    match tokio::net::lookup_host((host, 443)).await {
+        Ok(addrs) => addrs.map(|addr| addr.ip()).collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct IpRange {
+    pub addr: IpAddr,
+    pub prefix: u8,
+}
+
+impl IpRange {
+    pub fn parse(s: &str) -> Option<Self> {
+        let (addr, prefix) = match s.split_once('/') {
+            Some((addr, prefix)) => (addr.parse::<IpAddr>().ok()?, Some(prefix.parse().ok()?)),
+            None => (s.parse::<IpAddr>().ok()?, None),
+        };
+
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = prefix.unwrap_or(max);
+
+        (prefix <= max).then_some(Self { addr, prefix })
+    }
+
+    pub fn contains(&self, ip: &IpAddr) -> bool {
+        match (self.addr, ip) {
+            (IpAddr::V4(range), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                u32::from(range) & mask == u32::from(*ip) & mask
+            }
+            (IpAddr::V6(range), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                u128::from(range) & mask == u128::from(*ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    fn list(entries: &[&str]) -> InstanceBlackList {
+        BlackList(entries.iter().map(|entry| entry.to_string()).collect()).instances()
+    }
+
+    #[test]
+    fn range_contains_v4() {
+        let range = IpRange::parse("10.1.2.0/24").unwrap();
+        assert!(range.contains(&ip("10.1.2.0")));
+        assert!(range.contains(&ip("10.1.2.255")));
+        assert!(!range.contains(&ip("10.1.3.0")));
+        assert!(!range.contains(&ip("::ffff:10.1.2.1")));
+    }
+
+    #[test]
+    fn range_contains_v6() {
+        let range = IpRange::parse("2001:db8::/32").unwrap();
+        assert!(range.contains(&ip("2001:db8:ffff::1")));
+        assert!(!range.contains(&ip("2001:db9::1")));
+        assert!(!range.contains(&ip("10.1.2.1")));
+    }
+
+    #[test]
+    fn range_edge_prefixes() {
+        let single = IpRange::parse("1.2.3.4").unwrap();
+        assert_eq!(single.prefix, 32);
+        assert!(single.contains(&ip("1.2.3.4")));
+        assert!(!single.contains(&ip("1.2.3.5")));
+
+        let everything = IpRange::parse("0.0.0.0/0").unwrap();
+        assert!(everything.contains(&ip("255.255.255.255")));
+
+        let everything = IpRange::parse("::/0").unwrap();
+        assert!(everything.contains(&ip("2001:db8::1")));
+
+        assert!(IpRange::parse("1.2.3.4/33").is_none());
+        assert!(IpRange::parse("::/129").is_none());
+        assert!(IpRange::parse("example.com").is_none());
+    }
+
+    #[test]
+    fn wildcard_matches_subdomains_only() {
+        let list = list(&["*.example.com"]);
+        assert!(list.contains("a.example.com"));
+        assert!(list.contains("a.b.example.com"));
+        assert!(list.contains("A.Example.COM"));
+        assert!(!list.contains("example.com"));
+        assert!(!list.contains("badexample.com"));
+        assert!(!list.contains("example.com.evil.org"));
+    }
+
+    #[test]
+    fn hosts_and_ip_literals() {
+        let list = list(&[" Example.org ", "192.0.2.0/24"]);
+        assert!(list.contains("example.org"));
+        assert!(!list.contains("a.example.org"));
+        assert!(list.contains("192.0.2.7"));
+        assert!(!list.contains("192.0.3.7"));
+        assert!(list.contains_any(&[ip("198.51.100.1"), ip("192.0.2.1")]));
+        assert!(!list.contains_any(&[]));
+    }
 }
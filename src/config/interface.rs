@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 
-use crate::SavedFile;
+use crate::{Directory, OutboundConfig, SavedFile};
 
 #[derive(Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -33,6 +33,50 @@ impl LatencyThresholds {
     }
 }
 
+impl Default for InterfaceConfig {
+    fn default() -> Self {
+        Self {
+            regions_selector: [
+                ("Asia", "asia"),
+                ("Europe", "europe"),
+                ("Americas", "americas"),
+            ]
+            .into_iter()
+            .map(|(display, internal)| RegionSelectorEntry {
+                display: display.to_string(),
+                internal: internal.to_string(),
+            })
+            .collect(),
+            latency_thresholds: LatencyThresholds {
+                good: 600,
+                moderate: 3000,
+            },
+        }
+    }
+}
+
 impl SavedFile for InterfaceConfig {
-    const PATH: &'static str = ".config/vidiup/interface.json";
+    const DIR: Directory = Directory::Config;
+    const PATH: &'static str = "interface.json";
+}
+
+impl InterfaceConfig {
+    pub fn validate(&self, outbound: &OutboundConfig) -> Result<(), String> {
+        if let Some(entry) = self
+            .regions_selector
+            .iter()
+            .find(|entry| !outbound.offsets.0.contains_key(&entry.internal))
+        {
+            return Err(format!(
+                "region selector {} is not a region in outbound.json",
+                entry.internal
+            ));
+        }
+
+        if self.latency_thresholds.good > self.latency_thresholds.moderate {
+            return Err("latencyThresholds.good must not be above moderate".to_string());
+        }
+
+        Ok(())
+    }
 }
@@ -1,6 +1,8 @@
+use std::collections::HashMap;
+
 use serde::{Deserialize, Serialize};
 
-use crate::SavedFile;
+use crate::{Directory, OutboundConfig, RateLimitConfig, SavedFile};
 
 #[derive(Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -9,8 +11,188 @@ pub struct MasterConfig {
     pub timeout: u32,
     pub reverse_proxy: bool,
     pub port: u16,
+    #[serde(default)]
+    pub submissions: SubmissionsConfig,
+    #[serde(default)]
+    pub storage: StorageBackend,
+    #[serde(default)]
+    pub assignment: AssignmentConfig,
+    #[serde(default)]
+    pub enumeration: EnumerationConfig,
+    #[serde(default)]
+    pub geoip: GeoIpConfig,
+}
+
+impl Default for MasterConfig {
+    fn default() -> Self {
+        Self {
+            hot_per_region: 4,
+            timeout: 5000,
+            reverse_proxy: true,
+            port: 8082,
+            submissions: SubmissionsConfig::default(),
+            storage: StorageBackend::default(),
+            assignment: AssignmentConfig::default(),
+            enumeration: EnumerationConfig::default(),
+            geoip: GeoIpConfig::default(),
+        }
+    }
 }
 
 impl SavedFile for MasterConfig {
-    const PATH: &'static str = ".config/vidiup/master.json";
+    const DIR: Directory = Directory::Config;
+    const PATH: &'static str = "master.json";
+}
+
+impl MasterConfig {
+    pub fn validate(&self, outbound: &OutboundConfig) -> Result<(), String> {
+        if self.hot_per_region == 0 {
+            return Err("hotPerRegion must be at least 1".to_string());
+        }
+
+        if self.timeout == 0 {
+            return Err("timeout must be at least 1".to_string());
+        }
+
+        if self.submissions.per_ip.window == 0 || self.submissions.global.window == 0 {
+            return Err("submission rate limit windows must be at least 1".to_string());
+        }
+
+        if self.assignment.epoch == 0 || self.assignment.subset == 0 {
+            return Err("assignment epoch and subset must be at least 1".to_string());
+        }
+
+        if self.enumeration.window == 0 || self.enumeration.max_distinct == 0 {
+            return Err("enumeration window and maxDistinct must be at least 1".to_string());
+        }
+
+        if self
+            .enumeration
+            .blacklist
+            .is_some_and(|blacklist| blacklist.after == 0 || blacklist.duration == 0)
+        {
+            return Err("enumeration blacklist after and duration must be at least 1".to_string());
+        }
+
+        if let Some(region) = self
+            .geoip
+            .countries
+            .values()
+            .find(|region| !outbound.offsets.0.contains_key(*region))
+        {
+            return Err(format!("geoip.countries maps to unknown region {region}"));
+        }
+
+        Ok(())
+    }
+}
+
+/// Limits on `/api/v1/add`.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SubmissionsConfig {
+    pub per_ip: RateLimitConfig,
+    pub global: RateLimitConfig,
+    /// Most instances a single IP can be a backer of.
+    pub max_backed_per_ip: u32,
+    /// New submissions are rejected while this many instances are pending.
+    pub max_pending: u32,
+}
+
+impl Default for SubmissionsConfig {
+    fn default() -> Self {
+        Self {
+            per_ip: RateLimitConfig {
+                limit: 5,
+                window: 3600,
+            },
+            global: RateLimitConfig {
+                limit: 60,
+                window: 3600,
+            },
+            max_backed_per_ip: 10,
+            max_pending: 200,
+        }
+    }
+}
+
+/// Each client is only ever shown `subset` hot instances, reassigned every `epoch` seconds.
+#[derive(Serialize, Deserialize, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub struct AssignmentConfig {
+    pub epoch: u64,
+    pub subset: usize,
+}
+
+impl Default for AssignmentConfig {
+    fn default() -> Self {
+        Self {
+            epoch: 86400,
+            subset: 2,
+        }
+    }
+}
+
+/// Clients shown more than `max_distinct` instances within `window` seconds on `/api/v1/get`
+/// and `/finder` are treated as enumerating them.
+#[derive(Serialize, Deserialize, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub struct EnumerationConfig {
+    pub window: u64,
+    pub max_distinct: usize,
+    pub action: EnumerationAction,
+    /// Bans the client once it keeps asking, `None` to never do so.
+    pub blacklist: Option<EnumerationBlacklistConfig>,
+}
+
+impl Default for EnumerationConfig {
+    fn default() -> Self {
+        Self {
+            window: 86400,
+            max_distinct: 16,
+            action: EnumerationAction::default(),
+            blacklist: None,
+        }
+    }
+}
+
+/// What a client is given once it is treated as enumerating.
+#[derive(Serialize, Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum EnumerationAction {
+    /// Only instances it has already been shown, most recent first.
+    #[default]
+    Pin,
+    /// Nothing, until its oldest instances fall out of the window.
+    Throttle,
+}
+
+/// Bans for `duration` seconds after `after` requests while enumerating, see [`crate::Bans`].
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct EnumerationBlacklistConfig {
+    pub after: u32,
+    pub duration: u64,
+}
+
+/// Picks the region for requests that do not give one from the client's country.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct GeoIpConfig {
+    /// MaxMind country or city database, relative to the data directory. `None` to not detect
+    /// regions.
+    pub database: Option<String>,
+    /// ISO country codes to regions, countries not listed get every region.
+    pub countries: HashMap<String, String>,
+}
+
+/// Where instance records, polling records and poll history are kept.
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum StorageBackend {
+    /// `instances.json`, `pollingrecords.json` and `pollinghistory.json`, rewritten in full on
+    /// every save.
+    #[default]
+    Json,
+    /// `vidiup.db` in the data directory, written incrementally. Existing JSON files are
+    /// imported the first time it is used.
+    Sqlite,
 }
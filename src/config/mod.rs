@@ -1,8 +1,10 @@
+mod admin;
 mod blacklist;
 mod interface;
 mod master;
 mod outbound;
 
+pub use admin::*;
 pub use blacklist::*;
 pub use interface::*;
 pub use master::*;
@@ -2,7 +2,7 @@ use std::collections::HashMap;
 
 use serde::{Deserialize, Serialize};
 
-use crate::SavedFile;
+use crate::{Directory, SavedFile, ALL_REGIONS};
 
 #[derive(Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -11,10 +11,157 @@ pub struct OutboundConfig {
     pub offsets: OffsetsConfig,
     pub poll_probabilities: PollProbabilitiesConfig,
     pub check_interval: u64,
+    #[serde(default)]
+    pub weighting: WeightingConfig,
+    #[serde(default)]
+    pub demotion: DemotionConfig,
+    #[serde(default)]
+    pub history: HistoryConfig,
+    #[serde(default)]
+    pub sample_rotation: SampleRotation,
+    #[serde(default)]
+    pub health: HealthConfig,
+    #[serde(default)]
+    pub hysteresis: HysteresisConfig,
+    #[serde(default)]
+    pub retention: RetentionConfig,
+}
+
+impl Default for OutboundConfig {
+    fn default() -> Self {
+        Self {
+            polling: PollingConfig {
+                interval: 1200,
+                features: PollingFeaturesConfig {
+                    video: true,
+                    playlist: true,
+                    search: true,
+                    channel: true,
+                    playback: false,
+                    comments: false,
+                    captions: false,
+                    trending: false,
+                    popular: false,
+                },
+                max_concurrent: 4,
+            },
+            offsets: OffsetsConfig(HashMap::from([
+                ("americas".to_string(), -100),
+                ("asia".to_string(), -200),
+                ("europe".to_string(), 0),
+            ])),
+            poll_probabilities: PollProbabilitiesConfig {
+                hot: 1.,
+                recovered: 1.,
+                recovering: 1.,
+                dead: 0.2,
+                stashed_recovering: 1.,
+                stashed_dead: 0.2,
+                stashed: 1.,
+                pending: 0.8,
+            },
+            check_interval: 300,
+            weighting: WeightingConfig::default(),
+            demotion: DemotionConfig::default(),
+            history: HistoryConfig::default(),
+            sample_rotation: SampleRotation::Every(3600),
+            health: HealthConfig::default(),
+            hysteresis: HysteresisConfig::default(),
+            retention: RetentionConfig::default(),
+        }
+    }
 }
 
 impl SavedFile for OutboundConfig {
-    const PATH: &'static str = ".config/vidiup/outbound.json";
+    const DIR: Directory = Directory::Config;
+    const PATH: &'static str = "outbound.json";
+}
+
+impl OutboundConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.offsets.0.is_empty() {
+            return Err("offsets must list at least one region".to_string());
+        }
+
+        if self.offsets.0.contains_key(ALL_REGIONS) {
+            return Err(format!("{ALL_REGIONS} cannot be used as a region name"));
+        }
+
+        if self.polling.max_concurrent == 0 {
+            return Err("polling.maxConcurrent must be at least 1".to_string());
+        }
+
+        if self.check_interval == 0 {
+            return Err("checkInterval must be at least 1".to_string());
+        }
+
+        let probabilities = &self.poll_probabilities;
+        if [
+            probabilities.hot,
+            probabilities.recovered,
+            probabilities.recovering,
+            probabilities.dead,
+            probabilities.stashed_recovering,
+            probabilities.stashed_dead,
+            probabilities.stashed,
+            probabilities.pending,
+        ]
+        .iter()
+        .any(|probability| !(0. ..=1.).contains(probability))
+        {
+            return Err("pollProbabilities must be between 0 and 1".to_string());
+        }
+
+        if !(0. ..=1.).contains(&self.weighting.smoothing)
+            || self.weighting.min <= 0.
+            || self.weighting.min > self.weighting.max
+        {
+            return Err(
+                "weighting needs smoothing between 0 and 1, and 0 < min <= max".to_string(),
+            );
+        }
+
+        if self.history.size == 0 {
+            return Err("history.size must be at least 1".to_string());
+        }
+
+        if let SampleRotation::Every(0) = self.sample_rotation {
+            return Err("sampleRotation interval must be at least 1".to_string());
+        }
+
+        let thresholds = &self.health.thresholds;
+        if !(0. <= thresholds.dead
+            && thresholds.dead <= thresholds.recovering
+            && thresholds.recovering <= thresholds.hot
+            && thresholds.hot <= 1.)
+        {
+            return Err("health.thresholds needs 0 <= dead <= recovering <= hot <= 1".to_string());
+        }
+
+        if self
+            .health
+            .importance
+            .all()
+            .iter()
+            .any(|importance| !(importance.weight >= 0. && importance.weight.is_finite()))
+        {
+            return Err("health.importance weights must be at least 0".to_string());
+        }
+
+        if self.hysteresis.successes == 0 || self.hysteresis.failures == 0 {
+            return Err("hysteresis successes and failures must be at least 1".to_string());
+        }
+
+        if let (Some(archive), Some(delete)) =
+            (self.retention.archive_after, self.retention.delete_after)
+        {
+            if delete <= archive {
+                return Err("retention.deleteAfter must be longer than archiveAfter".to_string());
+            }
+        }
+
+        Ok(())
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -43,6 +190,214 @@ impl PollProbabilitiesConfig {
     }
 }
 
+/// How hot instance weights follow their latency.
+///
+/// Each poll folds the new score into an exponential moving average with factor `smoothing`,
+/// the weight is then the region's average latency divided by the instance's own, clamped to
+/// `min..=max`.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct WeightingConfig {
+    pub smoothing: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl Default for WeightingConfig {
+    fn default() -> Self {
+        Self {
+            smoothing: 0.3,
+            min: 0.1,
+            max: 10.,
+        }
+    }
+}
+
+/// Instances slower than `sigmas` standard deviations above their region's mean are demoted to
+/// `recovering`, only once the region has at least `min_samples` polled instances.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DemotionConfig {
+    pub sigmas: f64,
+    pub min_samples: u32,
+}
+
+impl Default for DemotionConfig {
+    fn default() -> Self {
+        Self {
+            sigmas: 2.,
+            min_samples: 3,
+        }
+    }
+}
+
+/// Keeps the last `size` polls of every instance. An instance failing a poll is only killed if
+/// its uptime over the last 24 hours is below `flaky_uptime`, otherwise it is put in recovering.
+///
+/// The uptimes shown per instance are left out for windows longer than `size` polls reach back,
+/// the default of 720 polls 20 minutes apart covers 10 days, so no month.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryConfig {
+    pub size: usize,
+    pub flaky_uptime: f64,
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        Self {
+            size: 720,
+            flaky_uptime: 0.8,
+        }
+    }
+}
+
+/// How a poll's outcomes are classified.
+///
+/// Each poll gets a health score, the weighted share of the expected features that worked. It
+/// is well at `thresholds.hot` or above if no required feature failed, and dead at
+/// `thresholds.dead` or below. Anything in between is put in recovering, except that dead
+/// instances scoring below `thresholds.recovering` are left dead.
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct HealthConfig {
+    #[serde(default)]
+    pub importance: FeatureImportanceConfig,
+    #[serde(default)]
+    pub thresholds: HealthThresholdsConfig,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct FeatureImportanceConfig {
+    pub video: FeatureImportance,
+    pub playlist: FeatureImportance,
+    pub channel: FeatureImportance,
+    pub search: FeatureImportance,
+    pub playback: FeatureImportance,
+    pub comments: FeatureImportance,
+    pub captions: FeatureImportance,
+    pub trending: FeatureImportance,
+    pub popular: FeatureImportance,
+}
+
+impl FeatureImportanceConfig {
+    pub fn all(&self) -> [FeatureImportance; 9] {
+        [
+            self.video,
+            self.playlist,
+            self.channel,
+            self.search,
+            self.playback,
+            self.comments,
+            self.captions,
+            self.trending,
+            self.popular,
+        ]
+    }
+}
+
+impl Default for FeatureImportanceConfig {
+    fn default() -> Self {
+        let optional = |weight| FeatureImportance {
+            weight,
+            required: false,
+        };
+
+        Self {
+            video: FeatureImportance {
+                weight: 5.,
+                required: true,
+            },
+            playlist: optional(2.),
+            channel: optional(2.),
+            search: optional(1.),
+            playback: optional(5.),
+            comments: optional(1.),
+            captions: optional(1.),
+            trending: optional(1.),
+            popular: optional(1.),
+        }
+    }
+}
+
+/// A `required` feature failing keeps the instance from being well, whatever its score.
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct FeatureImportance {
+    pub weight: f64,
+    #[serde(default)]
+    pub required: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct HealthThresholdsConfig {
+    pub hot: f64,
+    pub recovering: f64,
+    pub dead: f64,
+}
+
+impl Default for HealthThresholdsConfig {
+    fn default() -> Self {
+        Self {
+            hot: 0.9,
+            recovering: 0.5,
+            dead: 0.,
+        }
+    }
+}
+
+/// An instance is only revived after `successes` well polls in a row, and only rested or killed
+/// after `failures` polls in a row that were not well.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct HysteresisConfig {
+    pub successes: u32,
+    pub failures: u32,
+}
+
+impl Default for HysteresisConfig {
+    fn default() -> Self {
+        Self {
+            successes: 1,
+            failures: 1,
+        }
+    }
+}
+
+/// Dead and stashed dead instances are moved to `archive.json` once they have been dead for
+/// `archive_after` seconds, and deleted from it after `delete_after`. `None` does neither.
+///
+/// With `remember`, deleted addresses are kept in the archive so submitting one again says
+/// it was listed before.
+#[derive(Serialize, Deserialize, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub struct RetentionConfig {
+    pub archive_after: Option<u64>,
+    pub delete_after: Option<u64>,
+    pub remember: bool,
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        Self {
+            archive_after: None,
+            delete_after: None,
+            remember: true,
+        }
+    }
+}
+
+/// How often the video, playlist, channel and search samples are re-picked from `sampleset.json`.
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum SampleRotation {
+    #[default]
+    Never,
+    /// Every poll uses its own samples.
+    PerPoll,
+    /// Re-pick the global samples every this many seconds.
+    Every(u64),
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct PollingConfig {
@@ -58,6 +413,19 @@ pub struct PollingFeaturesConfig {
     pub playlist: bool,
     pub search: bool,
     pub channel: bool,
+    /// Fetches the start of the sample video's stream, needs video samples.
+    #[serde(default)]
+    pub playback: bool,
+    /// Needs video samples that have comments.
+    #[serde(default)]
+    pub comments: bool,
+    /// Needs video samples that have captions.
+    #[serde(default)]
+    pub captions: bool,
+    #[serde(default)]
+    pub trending: bool,
+    #[serde(default)]
+    pub popular: bool,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
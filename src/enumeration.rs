@@ -0,0 +1,146 @@
+use std::{collections::HashMap, time::Duration};
+
+use tokio::time::Instant;
+
+use crate::{assign, Bans, EnumerationAction, EnumerationConfig, HotRecord, ENUMERATION, METRICS};
+
+/// What a client may be shown on `/api/v1/get` and `/finder`.
+pub enum Verdict {
+    Allow,
+    /// Only one of these, most recently shown first.
+    Pin(Vec<String>),
+    Throttle,
+}
+
+impl Verdict {
+    /// Picks from `hot` as allowed, see [`assign`].
+    pub fn pick<'a>(&self, hot: &[&'a HotRecord], client: &str) -> Option<&'a HotRecord> {
+        match self {
+            Self::Allow => assign(hot, client),
+            Self::Pin(shown) => shown.iter().find_map(|address| {
+                hot.iter()
+                    .find(|record| record.instance.address == *address)
+                    .copied()
+            }),
+            Self::Throttle => None,
+        }
+    }
+}
+
+/// Which instances each client has been shown within the window, keyed by [`crate::client_key`].
+pub struct EnumerationTracker {
+    config: EnumerationConfig,
+    clients: HashMap<String, Client>,
+}
+
+#[derive(Default)]
+struct Client {
+    shown: HashMap<String, Instant>,
+    /// Requests made while over the limit.
+    flagged: u32,
+}
+
+impl EnumerationTracker {
+    pub fn new(config: EnumerationConfig) -> Self {
+        Self {
+            config,
+            clients: HashMap::new(),
+        }
+    }
+
+    pub fn set_config(&mut self, config: EnumerationConfig) {
+        self.config = config;
+    }
+
+    /// Forgets instances shown over a window ago, and clients left with none.
+    pub fn prune(&mut self) {
+        let now = Instant::now();
+        let window = Duration::from_secs(self.config.window);
+
+        self.clients.retain(|_, client| {
+            client
+                .shown
+                .retain(|_, at| now.duration_since(*at) < window);
+            !client.shown.is_empty()
+        });
+    }
+
+    /// Returns what `client` may be shown, and for how long to ban it if it should now be.
+    pub fn check(&mut self, client: &str) -> (Verdict, Option<u64>) {
+        let now = Instant::now();
+        let window = Duration::from_secs(self.config.window);
+
+        let Some(entry) = self.clients.get_mut(client) else {
+            return (Verdict::Allow, None);
+        };
+
+        // the rest are pruned periodically, see `prune`
+        entry.shown.retain(|_, at| now.duration_since(*at) < window);
+
+        if entry.shown.len() < self.config.max_distinct {
+            entry.flagged = 0;
+            return (Verdict::Allow, None);
+        }
+
+        entry.flagged += 1;
+        let ban = self
+            .config
+            .blacklist
+            .filter(|blacklist| entry.flagged == blacklist.after)
+            .map(|blacklist| blacklist.duration);
+        if ban.is_some() {
+            entry.flagged = 0;
+        }
+
+        let verdict = match self.config.action {
+            EnumerationAction::Pin => {
+                let mut shown = entry.shown.iter().collect::<Vec<_>>();
+                shown.sort_by(|a, b| b.1.cmp(a.1));
+                Verdict::Pin(
+                    shown
+                        .into_iter()
+                        .map(|(address, _)| address.clone())
+                        .collect(),
+                )
+            }
+            EnumerationAction::Throttle => Verdict::Throttle,
+        };
+
+        (verdict, ban)
+    }
+
+    /// Records that `client` has been shown `address`.
+    pub fn shown(&mut self, client: &str, address: &str) {
+        self.clients
+            .entry(client.to_string())
+            .or_default()
+            .shown
+            .insert(address.to_string(), Instant::now());
+    }
+}
+
+/// Checks `client` before it is shown an instance, banning it if it has been enumerating for too
+/// long.
+pub async fn guard(client: &str) -> Verdict {
+    let (verdict, ban) = ENUMERATION.get().unwrap().lock().unwrap().check(client);
+
+    if !matches!(verdict, Verdict::Allow) {
+        METRICS.get().unwrap().lock().unwrap().enumeration_flagged();
+    }
+
+    if let Some(duration) = ban {
+        Bans::ban(client, duration).await;
+    }
+
+    verdict
+}
+
+/// Records that `client` has been shown `address`.
+pub fn shown(client: &str, address: &str) {
+    ENUMERATION
+        .get()
+        .unwrap()
+        .lock()
+        .unwrap()
+        .shown(client, address);
+}
@@ -1,12 +1,22 @@
 use actix_files::NamedFile;
 use actix_web::{get, web::Path, Responder};
 
+use crate::Paths;
+
 #[get("/css/{path:.*}")]
 pub async fn css(params: Path<String>) -> impl Responder {
-    NamedFile::open_async(format!("./static/css/{params}")).await
+    NamedFile::open_async(format!(
+        "{}/css/{params}",
+        Paths::get().static_root.display()
+    ))
+    .await
 }
 
 #[get("/script/{path:.*}")]
 pub async fn scripts(params: Path<String>) -> impl Responder {
-    NamedFile::open_async(format!("./static/script/{params}")).await
+    NamedFile::open_async(format!(
+        "{}/script/{params}",
+        Paths::get().static_root.display()
+    ))
+    .await
 }
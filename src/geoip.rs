@@ -0,0 +1,74 @@
+use std::net::{IpAddr, SocketAddr};
+
+use maxminddb::{geoip2, Reader};
+
+use crate::{GeoIpConfig, Paths, GEOIP, MASTER_CONFIG, OUTBOUND_CONFIG};
+
+/// `region` value that asks for every region, instead of the detected one.
+pub const ALL_REGIONS: &str = "all";
+
+/// Offline country lookup, empty if no database is configured.
+#[derive(Default)]
+pub struct GeoIp(Option<Reader<Vec<u8>>>);
+
+impl GeoIp {
+    pub fn open(config: &GeoIpConfig) -> Result<Self, String> {
+        let Some(database) = &config.database else {
+            return Ok(Self::default());
+        };
+
+        let path = Paths::get().data.join(database);
+        Reader::open_readfile(&path)
+            .map(|reader| Self(Some(reader)))
+            .map_err(|e| format!("{}: {e}", path.display()))
+    }
+
+    /// ISO code of the country `ip` is in.
+    pub fn country(&self, ip: &str) -> Option<String> {
+        let reader = self.0.as_ref()?;
+        let country = reader.lookup::<geoip2::Country>(parse(ip)?).ok()?;
+        country
+            .country
+            .and_then(|country| country.iso_code)
+            .map(str::to_string)
+    }
+}
+
+/// Accepts addresses with a port or in brackets too, as they may come from `Forwarded`.
+fn parse(ip: &str) -> Option<IpAddr> {
+    ip.parse::<IpAddr>()
+        .or_else(|_| ip.parse::<SocketAddr>().map(|addr| addr.ip()))
+        .or_else(|_| ip.trim_start_matches('[').trim_end_matches(']').parse())
+        .ok()
+}
+
+/// Region of `ip` through `geoip.countries` in `master.json`.
+pub fn detect_region(ip: &str) -> Option<String> {
+    let country = GEOIP.get()?.country(ip)?;
+    let region = MASTER_CONFIG
+        .get()
+        .unwrap()
+        .geoip
+        .countries
+        .get(&country)?
+        .clone();
+
+    OUTBOUND_CONFIG
+        .get()
+        .unwrap()
+        .offsets
+        .0
+        .contains_key(&region)
+        .then_some(region)
+}
+
+/// Region to pick from for a request from `ip`, `None` for all of them.
+///
+/// Detected from `ip` if none was given, [`ALL_REGIONS`] skips detection.
+pub fn requested_region(region: Option<&str>, ip: &str) -> Option<String> {
+    match region {
+        Some(ALL_REGIONS) => None,
+        Some(region) => Some(region.to_string()),
+        None => detect_region(ip),
+    }
+}
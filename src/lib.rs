@@ -1,15 +1,33 @@
+pub mod address;
 pub mod api;
+pub mod metrics;
 
+mod assign;
+mod bans;
 mod config;
+mod enumeration;
 mod files;
+mod geoip;
 mod pages;
+mod paths;
+mod ratelimit;
+mod reload;
 mod saved_file;
 mod storage;
 mod values;
 
+pub use address::AddressError;
+pub use assign::*;
+pub use bans::*;
 pub use config::*;
+pub use enumeration::*;
 pub use files::*;
+pub use geoip::*;
+pub use metrics::Metrics;
 pub use pages::*;
+pub use paths::*;
+pub use ratelimit::*;
+pub use reload::*;
 pub use saved_file::*;
 pub use storage::*;
 pub use values::*;
@@ -7,17 +7,12 @@ use actix_web::{
     App, HttpResponseBuilder, HttpServer,
 };
 
-use log::info;
+use log::{error, info};
 use simplelog::Config;
 use vidiup::*;
 
 #[tokio::main]
 async fn main() {
-    init().await;
-
-    let port = MASTER_CONFIG.get().unwrap().port;
-    let reverse_proxy = MASTER_CONFIG.get().unwrap().reverse_proxy;
-
     simplelog::TermLogger::init(
         simplelog::LevelFilter::Info,
         Config::default(),
@@ -26,6 +21,21 @@ async fn main() {
     )
     .unwrap();
 
+    match Paths::resolve(std::env::args().skip(1)) {
+        Ok(paths) => PATHS.set(paths).unwrap(),
+        Err(e) => {
+            error!("{e}");
+            eprintln!("usage: vidiup [--config-dir DIR] [--data-dir DIR] [--static-dir DIR]");
+            std::process::exit(1);
+        }
+    }
+
+    init().await;
+    watch();
+
+    let port = MASTER_CONFIG.get().unwrap().port;
+    let reverse_proxy = MASTER_CONFIG.get().unwrap().reverse_proxy;
+
     tokio::spawn(async {
         loop {
             {
@@ -50,17 +60,42 @@ async fn main() {
 
             if *CONCURRENT_POLLS.get().unwrap().lock().unwrap() != 0 {
                 tokio::spawn(async {
-                    let instances = {
-                        let instances = INSTANCES_RECORD.get().unwrap().lock().unwrap();
-                        instances.clone()
-                    };
-                    *INSTANCES_STATS.get().unwrap().lock().unwrap() = instances.stat();
-                    let _ = instances.save().await;
+                    let stats = INSTANCES_RECORD.get().unwrap().lock().unwrap().stat();
+                    *INSTANCES_STATS.get().unwrap().lock().unwrap() = stats;
+                    let _ = STORAGE.get().unwrap().save_instances().await;
                 });
             }
         }
     });
 
+    tokio::spawn(async {
+        loop {
+            // re-read every time, sampleRotation may change on reload
+            match OUTBOUND_CONFIG.get().unwrap().sample_rotation {
+                SampleRotation::Every(interval) => {
+                    tokio::time::sleep(Duration::from_secs(interval)).await;
+                    SAMPLESETS.get().unwrap().lock().unwrap().gen();
+                }
+                _ => tokio::time::sleep(Duration::from_secs(60)).await,
+            }
+        }
+    });
+
+    tokio::spawn(async {
+        loop {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            Bans::expire().await;
+            ENUMERATION.get().unwrap().lock().unwrap().prune();
+        }
+    });
+
+    tokio::spawn(async {
+        loop {
+            Archive::retain().await;
+            tokio::time::sleep(Duration::from_secs(3600)).await;
+        }
+    });
+
     HttpServer::new(move || {
         App::new()
             .wrap_fn(
@@ -78,7 +113,9 @@ async fn main() {
                     };
 
                     let blacklist = BLACKLISTED_IP.get().unwrap().lock().unwrap();
-                    let allowed = address.as_ref().is_some_and(|ip| !blacklist.contains(ip));
+                    let allowed = address
+                        .as_ref()
+                        .is_some_and(|ip| !blacklist.contains(ip) && !is_banned(ip));
 
                     if allowed {
                         if !reverse_proxy {
@@ -88,6 +125,7 @@ async fn main() {
                         }
                         srv.call(req)
                     } else {
+                        METRICS.get().unwrap().lock().unwrap().blocked_request();
                         info!(
                             "Blocked access attempt from {}",
                             address.unwrap_or("no address".to_string())
@@ -107,10 +145,13 @@ async fn main() {
                 Logger::default()
             })
             .service(api::scope())
+            .service(metrics::metrics)
             .service(css)
             .service(scripts)
             .service(home::home)
             .service(finder::finder)
+            .service(go::go)
+            .service(instance::instance)
             .service(add::add)
     })
     .bind(("0.0.0.0", port))
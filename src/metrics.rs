@@ -0,0 +1,170 @@
+use std::{collections::BTreeMap, fmt::Write};
+
+use actix_web::{get, http::header::ContentType, HttpResponse, Responder};
+
+use crate::{Outcome, CONCURRENT_POLLS, INSTANCES_RECORD, METRICS, POLL_QUEUE};
+
+/// Upper bounds of the probe latency histogram buckets, in milliseconds.
+const LATENCY_BUCKETS: [u32; 8] = [100, 250, 500, 1000, 2000, 3000, 5000, 10000];
+
+/// Counters for `/metrics`, everything else there is read from the current records.
+#[derive(Default)]
+pub struct Metrics {
+    latency: BTreeMap<&'static str, Histogram>,
+    failures: BTreeMap<(&'static str, &'static str), u64>,
+    submissions_accepted: u64,
+    submissions_rejected: BTreeMap<String, u64>,
+    blocked_requests: u64,
+    enumeration_flagged: u64,
+}
+
+#[derive(Default)]
+struct Histogram {
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    count: u64,
+    sum: u64,
+}
+
+impl Metrics {
+    /// Records the outcome of probing `feature` on an instance.
+    pub fn probe(&mut self, feature: &'static str, outcome: &Outcome) {
+        match outcome.latency() {
+            Some(latency) => {
+                let histogram = self.latency.entry(feature).or_default();
+                for (bucket, bound) in histogram.buckets.iter_mut().zip(LATENCY_BUCKETS) {
+                    if latency <= bound {
+                        *bucket += 1;
+                    }
+                }
+                histogram.count += 1;
+                histogram.sum += latency as u64;
+            }
+            None => *self.failures.entry((feature, outcome.kind())).or_default() += 1,
+        }
+    }
+
+    pub fn submission_accepted(&mut self) {
+        self.submissions_accepted += 1;
+    }
+
+    pub fn submission_rejected(&mut self, reason: String) {
+        *self.submissions_rejected.entry(reason).or_default() += 1;
+    }
+
+    pub fn blocked_request(&mut self) {
+        self.blocked_requests += 1;
+    }
+
+    pub fn enumeration_flagged(&mut self) {
+        self.enumeration_flagged += 1;
+    }
+
+    fn render(&self, out: &mut String) {
+        let _ = writeln!(
+            out,
+            "# HELP vidiup_probe_latency_milliseconds Latency of successful probes.\n\
+             # TYPE vidiup_probe_latency_milliseconds histogram"
+        );
+        for (feature, histogram) in self.latency.iter() {
+            for (count, bound) in histogram.buckets.iter().zip(LATENCY_BUCKETS) {
+                let _ = writeln!(
+                    out,
+                    "vidiup_probe_latency_milliseconds_bucket{{feature=\"{feature}\",le=\"{bound}\"}} {count}"
+                );
+            }
+            let _ = writeln!(
+                out,
+                "vidiup_probe_latency_milliseconds_bucket{{feature=\"{feature}\",le=\"+Inf\"}} {}\n\
+                 vidiup_probe_latency_milliseconds_sum{{feature=\"{feature}\"}} {}\n\
+                 vidiup_probe_latency_milliseconds_count{{feature=\"{feature}\"}} {}",
+                histogram.count, histogram.sum, histogram.count
+            );
+        }
+
+        let _ = writeln!(
+            out,
+            "# HELP vidiup_probe_failures_total Failed probes by feature and outcome.\n\
+             # TYPE vidiup_probe_failures_total counter"
+        );
+        for ((feature, outcome), count) in self.failures.iter() {
+            let _ = writeln!(
+                out,
+                "vidiup_probe_failures_total{{feature=\"{feature}\",outcome=\"{outcome}\"}} {count}"
+            );
+        }
+
+        let _ = writeln!(
+            out,
+            "# HELP vidiup_submissions_total Instance submissions by result.\n\
+             # TYPE vidiup_submissions_total counter\n\
+             vidiup_submissions_total{{result=\"accepted\"}} {}",
+            self.submissions_accepted
+        );
+        for (reason, count) in self.submissions_rejected.iter() {
+            let _ = writeln!(
+                out,
+                "vidiup_submissions_total{{result=\"rejected\",reason=\"{reason}\"}} {count}"
+            );
+        }
+
+        let _ = writeln!(
+            out,
+            "# HELP vidiup_blocked_requests_total Requests refused by the IP blacklist.\n\
+             # TYPE vidiup_blocked_requests_total counter\n\
+             vidiup_blocked_requests_total {}",
+            self.blocked_requests
+        );
+
+        let _ = writeln!(
+            out,
+            "# HELP vidiup_enumeration_flagged_total Requests pinned or throttled for enumerating instances.\n\
+             # TYPE vidiup_enumeration_flagged_total counter\n\
+             vidiup_enumeration_flagged_total {}",
+            self.enumeration_flagged
+        );
+    }
+}
+
+#[get("/metrics")]
+pub async fn metrics() -> impl Responder {
+    let mut out = String::new();
+
+    let _ = writeln!(
+        out,
+        "# HELP vidiup_instances Instances by region and bucket.\n\
+         # TYPE vidiup_instances gauge"
+    );
+    {
+        let records = INSTANCES_RECORD.get().unwrap().lock().unwrap();
+        let regions = records.0.iter().collect::<BTreeMap<_, _>>();
+
+        for (region, records) in regions {
+            for (bucket, count) in records.counts() {
+                let _ = writeln!(
+                    out,
+                    "vidiup_instances{{region=\"{region}\",bucket=\"{bucket}\"}} {count}"
+                );
+            }
+        }
+    }
+
+    // one at a time, pollers hold CONCURRENT_POLLS while taking POLL_QUEUE
+    let pollers = *CONCURRENT_POLLS.get().unwrap().lock().unwrap();
+    let queued = POLL_QUEUE.get().unwrap().lock().unwrap().len();
+    let _ = writeln!(
+        out,
+        "# HELP vidiup_poll_queue Instances waiting to be polled.\n\
+         # TYPE vidiup_poll_queue gauge\n\
+         vidiup_poll_queue {}\n\
+         # HELP vidiup_active_pollers Poll workers currently running.\n\
+         # TYPE vidiup_active_pollers gauge\n\
+         vidiup_active_pollers {}",
+        queued, pollers
+    );
+
+    METRICS.get().unwrap().lock().unwrap().render(&mut out);
+
+    HttpResponse::Ok()
+        .content_type(ContentType::plaintext())
+        .body(out)
+}
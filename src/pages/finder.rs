@@ -8,7 +8,8 @@ use actix_web::{
 use serde::Deserialize;
 
 use crate::{
-    RegionSelectorEntry, INSTANCES_RECORD, INTERFACE_CONFIG, OUTBOUND_CONFIG, POLLING_RECORD,
+    client_key, guard, requested_region, shown, Outcome, RegionSelectorEntry, Verdict, ALL_REGIONS,
+    INSTANCES_RECORD, INTERFACE_CONFIG, OUTBOUND_CONFIG, POLLING_RECORD,
 };
 
 #[derive(Deserialize)]
@@ -16,9 +17,10 @@ struct GetQuery {
     pub region: Option<String>,
 }
 
-fn selector(selected: &Option<String>) -> String {
+/// `detected` marks `selected` as picked from the client's address.
+fn selector(selected: &Option<String>, detected: bool) -> String {
     let mut selectors = vec![if selected.is_some() {
-        r#"<a href="/finder">All regions</a>"#.to_string()
+        format!(r#"<a href="?region={ALL_REGIONS}">All regions</a>"#)
     } else {
         r#"<span>All regions</span>"#.to_string()
     }];
@@ -27,7 +29,13 @@ fn selector(selected: &Option<String>) -> String {
         INTERFACE_CONFIG.get().unwrap().regions_selector.iter()
     {
         if selected.as_ref() == Some(internal) {
-            selectors.push(format!("<span>{display}</span>"))
+            if detected {
+                selectors.push(format!(
+                    r#"<span title="Picked from your location">{display} (auto)</span>"#
+                ))
+            } else {
+                selectors.push(format!("<span>{display}</span>"))
+            }
         } else {
             selectors.push(format!(r#"<a href="?region={internal}">{display}</a>"#))
         }
@@ -89,97 +97,70 @@ const NO_INSTANCE: &str = r#"<p id="subhead">
           </tr>
         </table>"#;
 
+const TOO_MANY_REQUESTS: &str = r#"<p id="subhead">
+        Too many requests, try again later.
+        </p>"#;
+
 #[get("/finder")]
 pub async fn finder(query: Query<GetQuery>, req: HttpRequest) -> HttpResponse {
     if query.region.as_ref().is_some_and(|region| {
-        !OUTBOUND_CONFIG
-            .get()
-            .unwrap()
-            .offsets
-            .0
-            .contains_key(region)
+        region != ALL_REGIONS
+            && !OUTBOUND_CONFIG
+                .get()
+                .unwrap()
+                .offsets
+                .0
+                .contains_key(region)
     }) {
         Redirect::to("/finder")
             .permanent()
             .respond_to(&req)
             .set_body(BoxBody::new(""))
     } else {
+        let ip = req
+            .connection_info()
+            .realip_remote_addr()
+            .unwrap_or_default()
+            .to_string();
+
         HttpResponse::Ok()
             .content_type(ContentType::html())
-            .body(finder_task(query).await)
+            .body(finder_task(query, &ip).await)
     }
 }
 
-async fn finder_task(query: Query<GetQuery>) -> String {
-    let instance = if let Some(region) = &query.region {
-        if let Some(region) = INSTANCES_RECORD
-            .get()
-            .unwrap()
-            .lock()
-            .unwrap()
-            .0
-            .get(region)
-        {
-            let hot = &region.hot;
-
-            if hot.is_empty() {
-                return construct(NO_INSTANCE, &selector(&query.region));
-            }
-
-            let sum = hot.iter().map(|entry| entry.weight).sum::<f64>();
+async fn finder_task(query: Query<GetQuery>, ip: &str) -> String {
+    let client = client_key(ip);
+    let region = requested_region(query.region.as_deref(), ip);
+    let regions = selector(&region, query.region.is_none());
 
-            let mut take = fastrand::f64() * sum;
-            let mut instance = hot.len() - 1;
-
-            for (index, record) in hot.iter().enumerate() {
-                take -= record.weight;
-
-                if take < 0. {
-                    instance = index;
-                    break;
-                }
-            }
+    let verdict = guard(&client).await;
+    if let Verdict::Throttle = verdict {
+        return construct(TOO_MANY_REQUESTS, &regions);
+    }
 
-            hot[instance].instance.clone()
-        } else {
-            return construct(NO_INSTANCE, &selector(&query.region));
-        }
-    } else {
+    let instance = {
         let records = INSTANCES_RECORD.get().unwrap().lock().unwrap();
-        let hot = records
-            .0
-            .values()
-            .flat_map(|item| &item.hot)
-            .collect::<Vec<_>>();
+        let hot = records.candidates(region.as_deref(), query.region.is_none(), |records| {
+            &records.hot
+        });
 
-        if hot.is_empty() {
-            return construct(NO_INSTANCE, &selector(&query.region));
+        match verdict.pick(&hot, &client) {
+            Some(entry) => entry.instance.clone(),
+            None => return construct(NO_INSTANCE, &regions),
         }
-
-        let sum = hot.iter().map(|entry| entry.weight).sum::<f64>();
-
-        let mut take = fastrand::f64() * sum;
-        let mut instance = hot.len() - 1;
-
-        for (index, record) in hot.iter().enumerate() {
-            take -= record.weight;
-
-            if take < 0. {
-                instance = index;
-                break;
-            }
-        }
-
-        hot[instance].instance.clone()
     };
 
-    let offset = *OUTBOUND_CONFIG
+    shown(&client, &instance.address);
+
+    let offset = OUTBOUND_CONFIG
         .get()
         .unwrap()
         .offsets
         .0
         .get(&instance.region)
-        .unwrap();
+        .copied()
+        .unwrap_or_default();
     let record = POLLING_RECORD
         .get()
         .unwrap()
@@ -188,80 +169,37 @@ async fn finder_task(query: Query<GetQuery>) -> String {
         .0
         .get(&instance.address)
         .cloned()
-        .clone()
         .unwrap_or_default();
 
     let instance = instance.address;
 
+    let features = &OUTBOUND_CONFIG.get().unwrap().polling.features;
+    let thresholds = &INTERFACE_CONFIG.get().unwrap().latency_thresholds;
+
     let mut headers = Vec::new();
     let mut stats = Vec::new();
 
-    if OUTBOUND_CONFIG.get().unwrap().polling.features.video {
-        headers.push("<th>Video</th>".to_string());
-        if let Some(latency) = record.video {
-            let latency = latency.saturating_add_signed(offset);
-            stats.push(format!(
-                r#"<td class="{}">{}ms</td>"#,
-                INTERFACE_CONFIG
-                    .get()
-                    .unwrap()
-                    .latency_thresholds
-                    .quality(latency),
-                latency
-            ));
-        } else {
-            stats.push(r#"<td class="inactive">-ms</td>"#.to_string());
-        }
-    }
-
-    if OUTBOUND_CONFIG.get().unwrap().polling.features.playlist {
-        headers.push("<th>Playlist</th>".to_string());
-        if let Some(latency) = record.playlist {
-            let latency = latency.saturating_add_signed(offset);
-            stats.push(format!(
-                r#"<td class="{}">{}ms</td>"#,
-                INTERFACE_CONFIG
-                    .get()
-                    .unwrap()
-                    .latency_thresholds
-                    .quality(latency),
-                latency
-            ));
-        } else {
-            stats.push(r#"<td class="inactive">-ms</td>"#.to_string());
+    for (enabled, name, outcome) in [
+        (features.video, "Video", &record.video),
+        (features.playlist, "Playlist", &record.playlist),
+        (features.channel, "Channel", &record.channel),
+        (features.search, "Search", &record.search),
+        (features.playback, "Playback", &record.playback),
+        (features.comments, "Comments", &record.comments),
+        (features.captions, "Captions", &record.captions),
+        (features.trending, "Trending", &record.trending),
+        (features.popular, "Popular", &record.popular),
+    ] {
+        if !enabled {
+            continue;
         }
-    }
-
-    if OUTBOUND_CONFIG.get().unwrap().polling.features.channel {
-        headers.push("<th>Channel</th>".to_string());
-        if let Some(latency) = record.channel {
-            let latency = latency.saturating_add_signed(offset);
-            stats.push(format!(
-                r#"<td class="{}">{}ms</td>"#,
-                INTERFACE_CONFIG
-                    .get()
-                    .unwrap()
-                    .latency_thresholds
-                    .quality(latency),
-                latency
-            ));
-        } else {
-            stats.push(r#"<td class="inactive">-ms</td>"#.to_string());
-        }
-    }
 
-    if OUTBOUND_CONFIG.get().unwrap().polling.features.search {
-        headers.push("<th>Search</th>".to_string());
-        if let Some(latency) = record.search {
+        headers.push(format!("<th>{name}</th>"));
+        if let Some(latency) = outcome.as_ref().and_then(Outcome::latency) {
             let latency = latency.saturating_add_signed(offset);
             stats.push(format!(
-                r#"<td class="{}">{}ms</td>"#,
-                INTERFACE_CONFIG
-                    .get()
-                    .unwrap()
-                    .latency_thresholds
-                    .quality(latency),
-                latency
+                r#"<td class="{}">{latency}ms</td>"#,
+                thresholds.quality(latency)
             ));
         } else {
             stats.push(r#"<td class="inactive">-ms</td>"#.to_string());
@@ -284,5 +222,5 @@ async fn finder_task(query: Query<GetQuery>) -> String {
           </tr>
         </table>"#
     );
-    construct(&html, &selector(&query.region))
+    construct(&html, &regions)
 }
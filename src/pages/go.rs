@@ -0,0 +1,91 @@
+use actix_web::{
+    http::header::{CacheControl, CacheDirective, LOCATION},
+    routes, HttpRequest, HttpResponse,
+};
+
+use crate::{
+    client_key, guard, requested_region, shown, Verdict, ALL_REGIONS, INSTANCES_RECORD,
+    OUTBOUND_CONFIG,
+};
+
+/// Splits `region=...` out of the query string if it names one of our regions or
+/// [`ALL_REGIONS`], everything else is passed on to the instance.
+fn split_region(query: &str) -> (Option<String>, String) {
+    let offsets = &OUTBOUND_CONFIG.get().unwrap().offsets;
+    let mut region = None;
+
+    let rest = query
+        .split('&')
+        .filter(|pair| match pair.strip_prefix("region=") {
+            Some(value)
+                if region.is_none() && (value == ALL_REGIONS || offsets.0.contains_key(value)) =>
+            {
+                region = Some(value.to_string());
+                false
+            }
+            _ => !pair.is_empty(),
+        })
+        .collect::<Vec<_>>()
+        .join("&");
+
+    (region, rest)
+}
+
+/// Picks from hot instances, or recovered ones if there are no hot instances.
+fn pick(
+    region: &Option<String>,
+    detected: bool,
+    verdict: &Verdict,
+    client: &str,
+) -> Option<String> {
+    let records = INSTANCES_RECORD.get().unwrap().lock().unwrap();
+
+    let mut hot = records.candidates(region.as_deref(), detected, |records| &records.hot);
+    if hot.is_empty() {
+        hot = records.candidates(region.as_deref(), detected, |records| &records.recovered);
+    }
+
+    verdict
+        .pick(&hot, client)
+        .map(|entry| entry.instance.address.clone())
+}
+
+#[routes]
+#[get("/go")]
+#[get("/go/{path:.*}")]
+pub async fn go(req: HttpRequest) -> HttpResponse {
+    let ip = req
+        .connection_info()
+        .realip_remote_addr()
+        .unwrap_or_default()
+        .to_string();
+    let client = client_key(&ip);
+
+    let verdict = guard(&client).await;
+    if let Verdict::Throttle = verdict {
+        return HttpResponse::TooManyRequests().body("Too many requests");
+    }
+
+    let (region, query) = split_region(req.query_string());
+    let detected = region.is_none();
+    let region = requested_region(region.as_deref(), &ip);
+
+    let Some(address) = pick(&region, detected, &verdict, &client) else {
+        return HttpResponse::ServiceUnavailable().body("No instances found");
+    };
+
+    shown(&client, &address);
+
+    // raw path, so anything percent-encoded stays that way
+    let path = req.path().strip_prefix("/go").unwrap_or_default();
+    let mut location = format!("https://{address}{path}");
+    if !query.is_empty() {
+        location.push('?');
+        location.push_str(&query);
+    }
+
+    HttpResponse::Found()
+        .insert_header((LOCATION, location))
+        .insert_header(CacheControl(vec![CacheDirective::NoStore]))
+        .finish()
+}
@@ -0,0 +1,171 @@
+use actix_web::{get, http::header::ContentType, web::Path, HttpResponse};
+use chrono::DateTime;
+
+use crate::{Health, InstanceStatus, Outcome, INTERFACE_CONFIG, OUTBOUND_CONFIG};
+
+fn construct(body: &str) -> String {
+    format!(
+        r#"<!doctype html>
+<html lang="en">
+  <head>
+    <meta charset="UTF-8" />
+    <meta name="viewport" content="width=device-width, initial-scale=1.0" />
+    <meta name="robots" content="noindex" />
+    <link rel="preconnect" href="https://fonts.googleapis.com" />
+    <link rel="preconnect" href="https://fonts.gstatic.com" crossorigin />
+    <link
+      href="https://fonts.googleapis.com/css2?family=Open+Sans:ital,wght@0,300..800;1,300..800&family=Roboto+Mono:ital,wght@0,100..700;1,100..700&display=swap"
+      rel="stylesheet"
+    />
+    <title>VidiUp - Invidious Health</title>
+    <link rel="stylesheet" href="/css/home.css" />
+  </head>
+  <body>
+    <div class="fullpage">
+      <div id="mainscreen">
+        <h1 id="title">Instance status</h1>
+        {body}
+      </div>
+    </div>
+  </body>
+</html>"#
+    )
+}
+
+const NOT_FOUND: &str = r#"<p id="subhead">
+        No such instance.
+        </p>"#;
+
+fn time(timestamp: u64) -> String {
+    DateTime::from_timestamp(timestamp as i64, 0)
+        .map(|time| time.format("%Y-%m-%d %H:%M UTC").to_string())
+        .unwrap_or_default()
+}
+
+fn health(health: Health) -> &'static str {
+    match health {
+        Health::Up => "up",
+        Health::Degraded => "degraded",
+        Health::Down => "down",
+    }
+}
+
+fn uptime(uptime: Option<f64>) -> String {
+    uptime
+        .map(|uptime| format!("{:.1}%", uptime * 100.))
+        .unwrap_or("-".to_string())
+}
+
+#[get("/instance/{address}")]
+pub async fn instance(address: Path<String>) -> HttpResponse {
+    let Some(status) = InstanceStatus::of(&address) else {
+        return HttpResponse::NotFound()
+            .content_type(ContentType::html())
+            .body(construct(NOT_FOUND));
+    };
+
+    let features = &OUTBOUND_CONFIG.get().unwrap().polling.features;
+    let thresholds = &INTERFACE_CONFIG.get().unwrap().latency_thresholds;
+    let latest = status.latest.clone().unwrap_or_default();
+
+    let mut headers = Vec::new();
+    let mut stats = Vec::new();
+
+    for (enabled, name, outcome) in [
+        (features.video, "Video", &latest.video),
+        (features.playlist, "Playlist", &latest.playlist),
+        (features.channel, "Channel", &latest.channel),
+        (features.search, "Search", &latest.search),
+        (features.playback, "Playback", &latest.playback),
+        (features.comments, "Comments", &latest.comments),
+        (features.captions, "Captions", &latest.captions),
+        (features.trending, "Trending", &latest.trending),
+        (features.popular, "Popular", &latest.popular),
+    ] {
+        if !enabled {
+            continue;
+        }
+
+        headers.push(format!("<th>{name}</th>"));
+        if let Some(latency) = outcome.as_ref().and_then(Outcome::latency) {
+            stats.push(format!(
+                r#"<td class="{}">{latency}ms</td>"#,
+                thresholds.quality(latency)
+            ));
+        } else {
+            stats.push(r#"<td class="inactive">-ms</td>"#.to_string());
+        }
+    }
+
+    let headers = headers.join("\n            ");
+    let stats = stats.join("\n            ");
+
+    let region = INTERFACE_CONFIG
+        .get()
+        .unwrap()
+        .regions_selector
+        .iter()
+        .find(|entry| entry.internal == status.region)
+        .map_or(status.region.clone(), |entry| entry.display.clone());
+    let last_polled = status.last_polled.map_or("never".to_string(), time);
+
+    let (day, week, month) = status
+        .history
+        .as_ref()
+        .map_or((None, None, None), |history| {
+            (
+                history.uptime.day,
+                history.uptime.week,
+                history.uptime.month,
+            )
+        });
+
+    let health_changes = if status.health_changes.is_empty() {
+        "<li>No health changes recorded</li>".to_string()
+    } else {
+        status
+            .health_changes
+            .iter()
+            .rev()
+            .map(|change| {
+                format!(
+                    "<li>{}: polled {} after {}</li>",
+                    time(change.at),
+                    health(change.to),
+                    health(change.from)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n          ")
+    };
+
+    let address = &status.address;
+    let state = status.state;
+
+    let html = format!(
+        r#"<p id="subhead">
+        Instance: <a href="https://{address}" target="_blank"><i>{address}</i></a>
+        </p>
+        <p>{region}, {state}, last polled {last_polled}</p>
+        <table id="record">
+          <tr>
+            {headers}
+          </tr>
+          <tr>
+            {stats}
+          </tr>
+        </table>
+        <p>Uptime: <span class="counter">{}</span> day, <span class="counter">{}</span> week, <span class="counter">{}</span> month</p>
+        <p>Health changes:</p>
+        <ul>
+          {health_changes}
+        </ul>"#,
+        uptime(day),
+        uptime(week),
+        uptime(month)
+    );
+
+    HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(construct(&html))
+}
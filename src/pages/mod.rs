@@ -1,3 +1,5 @@
 pub mod add;
 pub mod finder;
+pub mod go;
 pub mod home;
+pub mod instance;
@@ -0,0 +1,88 @@
+use std::{env, path::PathBuf};
+
+use crate::PATHS;
+
+/// Where vidiup keeps its files, resolved once at startup.
+///
+/// Each directory is taken from its command line option, then its environment variable, then
+/// the default.
+///
+/// | Directory | Option | Environment variable | Default |
+/// | --- | --- | --- | --- |
+/// | config | `--config-dir` | `VIDIUP_CONFIG_DIR` | `~/.config/vidiup` |
+/// | data | `--data-dir` | `VIDIUP_DATA_DIR` | `~/.local/share/vidiup` |
+/// | static | `--static-dir` | `VIDIUP_STATIC_DIR` | `./static` |
+#[derive(Debug, Clone)]
+pub struct Paths {
+    pub config: PathBuf,
+    pub data: PathBuf,
+    pub static_root: PathBuf,
+}
+
+impl Paths {
+    /// Resolves the directories from command line arguments (without the program name) and
+    /// the environment.
+    ///
+    /// The home directory is only needed for the config and data defaults, so with both given
+    /// it may be unset.
+    pub fn resolve(args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut config = env::var("VIDIUP_CONFIG_DIR").ok().map(PathBuf::from);
+        let mut data = env::var("VIDIUP_DATA_DIR").ok().map(PathBuf::from);
+        let mut static_root = env::var("VIDIUP_STATIC_DIR").ok().map(PathBuf::from);
+
+        let mut args = args;
+        while let Some(arg) = args.next() {
+            let (option, value) = match arg.split_once('=') {
+                Some((option, value)) => (option.to_string(), Some(value.to_string())),
+                None => (arg, None),
+            };
+
+            let target = match option.as_str() {
+                "--config-dir" => &mut config,
+                "--data-dir" => &mut data,
+                "--static-dir" => &mut static_root,
+                _ => return Err(format!("unknown option {option}")),
+            };
+
+            match value.or_else(|| args.next()) {
+                Some(value) => *target = Some(value.into()),
+                None => return Err(format!("{option} needs a directory")),
+            }
+        }
+
+        let in_home = |dir: Option<PathBuf>, default: &str, option: &str| match dir {
+            Some(dir) => Ok(dir),
+            None => dirs::home_dir()
+                .map(|home| home.join(default))
+                .ok_or_else(|| format!("no home directory to default to, set {option}")),
+        };
+
+        Ok(Self {
+            config: in_home(config, ".config/vidiup", "--config-dir")?,
+            data: in_home(data, ".local/share/vidiup", "--data-dir")?,
+            static_root: static_root.unwrap_or_else(|| PathBuf::from("./static")),
+        })
+    }
+
+    /// The paths in effect, resolved from the environment alone if none were set.
+    pub fn get() -> &'static Self {
+        PATHS.get_or_init(|| Self::resolve(std::iter::empty()).unwrap())
+    }
+}
+
+/// Which of the [`Paths`] a [`SavedFile`](crate::SavedFile) lives in.
+pub enum Directory {
+    Config,
+    Data,
+}
+
+impl Directory {
+    pub fn path(&self) -> &'static PathBuf {
+        let paths = Paths::get();
+
+        match self {
+            Self::Config => &paths.config,
+            Self::Data => &paths.data,
+        }
+    }
+}
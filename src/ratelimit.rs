@@ -0,0 +1,61 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use tokio::time::Instant;
+
+/// Allows `limit` hits per `window` seconds.
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct RateLimitConfig {
+    pub limit: u32,
+    pub window: u64,
+}
+
+/// Sliding window counter, keyed by client.
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    hits: HashMap<String, VecDeque<Instant>>,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self {
+            config,
+            hits: HashMap::new(),
+        }
+    }
+
+    pub fn set_config(&mut self, config: RateLimitConfig) {
+        self.config = config;
+    }
+
+    /// Records a hit from `key`, returns false if it is over the limit.
+    pub fn hit(&mut self, key: &str) -> bool {
+        let now = Instant::now();
+        let window = Duration::from_secs(self.config.window);
+
+        // drop clients that have been quiet for a whole window
+        self.hits.retain(|_, hits| {
+            hits.back()
+                .is_some_and(|last| now.duration_since(*last) < window)
+        });
+
+        let hits = self.hits.entry(key.to_string()).or_default();
+
+        while hits
+            .front()
+            .is_some_and(|first| now.duration_since(*first) >= window)
+        {
+            hits.pop_front();
+        }
+
+        if hits.len() >= self.config.limit as usize {
+            return false;
+        }
+
+        hits.push_back(now);
+        true
+    }
+}
@@ -0,0 +1,287 @@
+use std::{
+    sync::{Arc, RwLock},
+    time::{Duration, SystemTime},
+};
+
+use log::{error, info, warn};
+
+use crate::*;
+
+/// Config slot that can be swapped out at runtime.
+///
+/// Readers get an `Arc` of the config at the time of the call, so a reload never changes a
+/// config in the middle of its use.
+pub struct Reloadable<T>(RwLock<Option<Arc<T>>>);
+
+impl<T> Reloadable<T> {
+    pub const fn new() -> Self {
+        Self(RwLock::new(None))
+    }
+
+    pub fn get(&self) -> Option<Arc<T>> {
+        self.0.read().unwrap().clone()
+    }
+
+    pub fn set(&self, value: T) {
+        *self.0.write().unwrap() = Some(Arc::new(value));
+    }
+}
+
+impl<T> Default for Reloadable<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Every reloadable config, swapped in by [`reload`] in one step so readers never see new and
+/// old configs mixed.
+pub struct Configs {
+    pub master: Arc<MasterConfig>,
+    pub outbound: Arc<OutboundConfig>,
+    pub interface: Arc<InterfaceConfig>,
+    pub geoip: Arc<GeoIp>,
+}
+
+/// One config out of [`CONFIGS`].
+pub struct ConfigView<T>(fn(&Configs) -> &Arc<T>);
+
+impl<T> ConfigView<T> {
+    pub const fn new(select: fn(&Configs) -> &Arc<T>) -> Self {
+        Self(select)
+    }
+
+    pub fn get(&self) -> Option<Arc<T>> {
+        CONFIGS.get().map(|configs| (self.0)(&configs).clone())
+    }
+}
+
+/// Checks the configs against each other, as loaded on startup or reload.
+pub fn validate(
+    master: &MasterConfig,
+    outbound: &OutboundConfig,
+    interface: &InterfaceConfig,
+) -> Result<(), String> {
+    master
+        .validate(outbound)
+        .map_err(|e| format!("{}: {e}", MasterConfig::path().display()))?;
+    outbound
+        .validate()
+        .map_err(|e| format!("{}: {e}", OutboundConfig::path().display()))?;
+    interface
+        .validate(outbound)
+        .map_err(|e| format!("{}: {e}", InterfaceConfig::path().display()))
+}
+
+/// Rejects an `offsets` that drops a region instances are still listed under.
+fn check_regions(outbound: &OutboundConfig, records: &InstancesRecords) -> Result<(), String> {
+    match records.0.iter().find(|(region, records)| {
+        !outbound.offsets.0.contains_key(*region) && !records.all_instances().is_empty()
+    }) {
+        Some((region, _)) => Err(format!(
+            "{}: offsets: region {region} still has instances, move them first",
+            OutboundConfig::path().display()
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Loads and validates every reloadable config file, then swaps all of them in at once.
+///
+/// Nothing is changed if any of the files fail to load or validate.
+pub async fn reload() -> Result<(), String> {
+    let result = load_all().await;
+
+    let (master, outbound, interface, blacklists, geoip) = match result {
+        Ok(configs) => configs,
+        Err(e) => {
+            error!("Config reload rejected: {e}");
+            return Err(e);
+        }
+    };
+
+    let current = MASTER_CONFIG.get().unwrap();
+    if current.port != master.port
+        || current.reverse_proxy != master.reverse_proxy
+        || current.storage != master.storage
+    {
+        warn!("Changes to port, reverseProxy and storage only take effect after a restart");
+    }
+
+    SUBMISSIONS_PER_IP
+        .get()
+        .unwrap()
+        .lock()
+        .unwrap()
+        .set_config(master.submissions.per_ip);
+    SUBMISSIONS_GLOBAL
+        .get()
+        .unwrap()
+        .lock()
+        .unwrap()
+        .set_config(master.submissions.global);
+    ENUMERATION
+        .get()
+        .unwrap()
+        .lock()
+        .unwrap()
+        .set_config(master.enumeration);
+
+    CONFIGS.set(Configs {
+        master: Arc::new(master),
+        outbound: Arc::new(outbound),
+        interface: Arc::new(interface),
+        geoip: Arc::new(geoip),
+    });
+    blacklists.apply();
+    *BLACKLISTS.get().unwrap().lock().unwrap() = blacklists;
+
+    let blacklisted = INSTANCES_RECORD
+        .get()
+        .unwrap()
+        .lock()
+        .unwrap()
+        .blacklisted();
+    if remove_instances(&blacklisted).await {
+        for instance in blacklisted {
+            warn!("Removed blacklisted instance {instance}");
+        }
+    }
+
+    info!("Config reloaded");
+    Ok(())
+}
+
+#[allow(clippy::type_complexity)]
+async fn load_all() -> Result<
+    (
+        MasterConfig,
+        OutboundConfig,
+        InterfaceConfig,
+        BlackLists,
+        GeoIp,
+    ),
+    String,
+> {
+    let master = MasterConfig::load()
+        .await
+        .map_err(|e| format!("{}: {e}", MasterConfig::path().display()))?;
+    let outbound = OutboundConfig::load()
+        .await
+        .map_err(|e| format!("{}: {e}", OutboundConfig::path().display()))?;
+    let interface = InterfaceConfig::load()
+        .await
+        .map_err(|e| format!("{}: {e}", InterfaceConfig::path().display()))?;
+    let blacklists = BlackLists::load()
+        .await
+        .map_err(|e| format!("{}: {e}", BlackLists::path().display()))?;
+
+    validate(&master, &outbound, &interface)?;
+    check_regions(&outbound, &INSTANCES_RECORD.get().unwrap().lock().unwrap())?;
+
+    let geoip = GeoIp::open(&master.geoip)
+        .map_err(|e| format!("{}: geoip: {e}", MasterConfig::path().display()))?;
+
+    Ok((master, outbound, interface, blacklists, geoip))
+}
+
+/// Reloads on SIGHUP, and whenever one of the config files is modified.
+pub fn watch() {
+    #[cfg(unix)]
+    tokio::spawn(async {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut hangup = match signal(SignalKind::hangup()) {
+            Ok(hangup) => hangup,
+            Err(e) => {
+                warn!("Cannot listen for SIGHUP: {e}");
+                return;
+            }
+        };
+
+        while hangup.recv().await.is_some() {
+            info!("SIGHUP received, reloading config");
+            let _ = reload().await;
+        }
+    });
+
+    tokio::spawn(async {
+        let mut last = modified().await;
+
+        loop {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+
+            let current = modified().await;
+            if current != last {
+                info!("Config files changed, reloading");
+                let _ = reload().await;
+                last = current;
+            }
+        }
+    });
+}
+
+async fn modified() -> Vec<Option<SystemTime>> {
+    let mut out = Vec::new();
+
+    for path in [
+        MasterConfig::path(),
+        OutboundConfig::path(),
+        InterfaceConfig::path(),
+        BlackLists::path(),
+    ] {
+        out.push(
+            tokio::fs::metadata(path)
+                .await
+                .and_then(|metadata| metadata.modified())
+                .ok(),
+        );
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn records(region: &str, instances: &[&str]) -> InstancesRecords {
+        let records = RegionRecords {
+            pending: instances
+                .iter()
+                .map(|address| InstanceRecord {
+                    address: address.to_string(),
+                    region: region.to_string(),
+                    backer: Vec::new(),
+                    streak: Streak::default(),
+                })
+                .collect(),
+            ..Default::default()
+        };
+
+        InstancesRecords(HashMap::from([(region.to_string(), records)]))
+    }
+
+    fn outbound(regions: &[&str]) -> OutboundConfig {
+        let mut outbound = OutboundConfig::default();
+        outbound.offsets.0 = regions
+            .iter()
+            .map(|region| (region.to_string(), 0))
+            .collect();
+        outbound
+    }
+
+    #[test]
+    fn dropping_region_with_instances_rejected() {
+        let records = records("eu", &["a.example"]);
+
+        assert!(check_regions(&outbound(&["eu", "us"]), &records).is_ok());
+        assert!(check_regions(&outbound(&["us"]), &records).is_err());
+    }
+
+    #[test]
+    fn dropping_empty_region_allowed() {
+        assert!(check_regions(&outbound(&["us"]), &records("eu", &[])).is_ok());
+    }
+}
@@ -1,29 +1,210 @@
 use async_trait::async_trait;
+use log::{info, warn};
 use serde::{de::DeserializeOwned, Serialize};
-use std::error::Error;
+use std::{
+    collections::HashMap,
+    error::Error,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, OnceLock},
+    time::SystemTime,
+};
 use tokio::{fs, io::AsyncWriteExt};
 
+use crate::Directory;
+
+type SaveLock = Arc<tokio::sync::Mutex<()>>;
+
+/// One lock per file, so concurrent saves of the same file are written one after another.
+static SAVE_LOCKS: OnceLock<Mutex<HashMap<PathBuf, SaveLock>>> = OnceLock::new();
+
 #[async_trait]
 pub trait SavedFile: Serialize + DeserializeOwned {
+    const DIR: Directory;
+    /// File name within [`SavedFile::DIR`].
     const PATH: &'static str;
+    /// Number of previous generations kept as `[PATH].1` (newest) to `[PATH].[BACKUPS]`.
+    const BACKUPS: usize = 3;
+    /// Minimum seconds between generations, saves in between only replace the file itself.
+    const BACKUP_INTERVAL: u64 = 60 * 60;
+
+    fn path() -> PathBuf {
+        Self::DIR.path().join(Self::PATH)
+    }
 
+    /// Loads the file, falling back to the newest backup that parses.
     async fn load() -> Result<Self, Box<dyn Error>> {
-        let path = dirs::home_dir().unwrap().join(Self::PATH);
-        let content = fs::read_to_string(path).await?;
+        let path = Self::path();
+
+        let err = match read(&path).await {
+            Ok(loaded) => return Ok(loaded),
+            Err(e) => e,
+        };
+
+        for generation in 1..=Self::BACKUPS {
+            let backup = backup_path(&path, generation);
+
+            if let Ok(loaded) = read(&backup).await {
+                warn!(
+                    "Could not load {}: {err}, using backup {}",
+                    path.display(),
+                    backup.display()
+                );
+                return Ok(loaded);
+            }
+        }
+
+        Err(err)
+    }
 
-        Ok(serde_json::from_str(&content)?)
+    /// Like [`SavedFile::load`], but `None` when neither the file nor any backup exists.
+    async fn load_existing() -> Result<Option<Self>, Box<dyn Error>> {
+        let path = Self::path();
+
+        let mut exists = fs::try_exists(&path).await.unwrap_or(true);
+        for generation in 1..=Self::BACKUPS {
+            exists |= fs::try_exists(backup_path(&path, generation))
+                .await
+                .unwrap_or(true);
+        }
+
+        if exists {
+            Self::load().await.map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Like [`SavedFile::load`], but on first run, when neither the file nor any backup exists,
+    /// writes the defaults to disk and uses those.
+    async fn load_or_create() -> Result<Self, Box<dyn Error>>
+    where
+        Self: Default + Sync,
+    {
+        if let Some(loaded) = Self::load_existing().await? {
+            return Ok(loaded);
+        }
+
+        let path = Self::path();
+
+        // pretty printed, as these are mostly config files for people to edit
+        let created = Self::default();
+        let _guard = save_lock(&path).lock_owned().await;
+        write(
+            &path,
+            Self::BACKUPS,
+            Self::BACKUP_INTERVAL,
+            serde_json::to_string_pretty(&created)?,
+        )
+        .await?;
+        info!("Created {} with defaults", path.display());
+
+        Ok(created)
     }
 
+    /// Writes to a temporary file, syncs it to disk, then renames it over the old file, so a
+    /// crash mid-save never leaves a truncated file behind.
     async fn save(&self) -> Result<(), Box<dyn Error>> {
-        let path = dirs::home_dir().unwrap().join(Self::PATH);
+        let path = Self::path();
         let content = serde_json::to_string(&self)?;
 
-        let mut file = fs::OpenOptions::new()
+        let _guard = save_lock(&path).lock_owned().await;
+        Ok(write(&path, Self::BACKUPS, Self::BACKUP_INTERVAL, content).await?)
+    }
+
+    /// Like [`SavedFile::save`], but only takes the snapshot once earlier saves of the file are
+    /// done, so a snapshot of shared state never overwrites a newer one.
+    async fn save_latest<F>(snapshot: F) -> Result<(), Box<dyn Error>>
+    where
+        F: FnOnce() -> Self + Send,
+    {
+        let path = Self::path();
+
+        let _guard = save_lock(&path).lock_owned().await;
+        let content = serde_json::to_string(&snapshot())?;
+        Ok(write(&path, Self::BACKUPS, Self::BACKUP_INTERVAL, content).await?)
+    }
+}
+
+fn save_lock(path: &Path) -> SaveLock {
+    SAVE_LOCKS
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap()
+        .entry(path.to_path_buf())
+        .or_default()
+        .clone()
+}
+
+/// Writes `content` to `path`, rotating backups if the newest is over `interval` seconds old.
+/// Callers hold the file's [`save_lock`].
+async fn write(path: &Path, backups: usize, interval: u64, content: String) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+
+    let temp = with_suffix(path, "tmp");
+    let mut file = fs::File::create(&temp).await?;
+    file.write_all(content.as_bytes()).await?;
+    file.sync_all().await?;
+    drop(file);
+
+    if fs::try_exists(path).await.unwrap_or(false)
+        && backups != 0
+        && backup_due(path, interval).await
+    {
+        for generation in (1..backups).rev() {
+            let _ = fs::rename(
+                backup_path(path, generation),
+                backup_path(path, generation + 1),
+            )
+            .await;
+        }
+        fs::rename(path, backup_path(path, 1)).await?;
+
+        // so the interval counts from the rotation, not from when the backup was last written
+        if let Ok(backup) = fs::OpenOptions::new()
             .write(true)
-            .truncate(true)
-            .open(path)
-            .await?;
+            .open(backup_path(path, 1))
+            .await
+        {
+            let _ = backup.into_std().await.set_modified(SystemTime::now());
+        }
+    }
 
-        Ok(file.write_all(content.as_bytes()).await?)
+    fs::rename(&temp, path).await?;
+
+    // make the renames themselves durable
+    if let Some(parent) = path.parent() {
+        if let Ok(dir) = fs::File::open(parent).await {
+            let _ = dir.sync_all().await;
+        }
     }
+
+    Ok(())
+}
+
+/// Whether the newest backup is missing or older than `interval` seconds.
+async fn backup_due(path: &Path, interval: u64) -> bool {
+    fs::metadata(backup_path(path, 1))
+        .await
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| modified.elapsed().ok())
+        .is_none_or(|age| age.as_secs() >= interval)
+}
+
+async fn read<T: DeserializeOwned>(path: &Path) -> Result<T, Box<dyn Error + Send + Sync>> {
+    let content = fs::read_to_string(path).await?;
+    Ok(serde_json::from_str(&content)?)
+}
+
+fn backup_path(path: &Path, generation: usize) -> PathBuf {
+    with_suffix(path, &generation.to_string())
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(".");
+    path.push(suffix);
+    path.into()
 }
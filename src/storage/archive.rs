@@ -0,0 +1,306 @@
+use std::collections::HashSet;
+
+use chrono::{DateTime, Duration, Utc};
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+
+use crate::*;
+
+/// Instances that were dead for longer than `retention.archiveAfter`, and optionally the ones
+/// since deleted after `retention.deleteAfter`.
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct Archive {
+    pub archived: Vec<ArchivedRecord>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub purged: Vec<PurgedRecord>,
+}
+
+impl SavedFile for Archive {
+    const DIR: Directory = Directory::Data;
+    const PATH: &'static str = "archive.json";
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchivedRecord {
+    pub instance: InstanceRecord,
+    pub dead_since: DateTime<Utc>,
+    pub archived_on: DateTime<Utc>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PurgedRecord {
+    pub address: String,
+    pub region: String,
+    pub dead_since: DateTime<Utc>,
+    pub purged_on: DateTime<Utc>,
+}
+
+impl Archive {
+    /// Archives instances dead for longer than `retention.archiveAfter`, and deletes archived
+    /// ones dead for longer than `retention.deleteAfter`.
+    pub async fn retain() {
+        let config = OUTBOUND_CONFIG.get().unwrap().retention;
+        let now = Utc::now();
+        let cutoff = config
+            .archive_after
+            .map(|after| now - Duration::seconds(after as i64));
+
+        let archived = match cutoff {
+            Some(cutoff) => INSTANCES_RECORD
+                .get()
+                .unwrap()
+                .lock()
+                .unwrap()
+                .dead_before(cutoff),
+            None => Vec::new(),
+        };
+        let addresses = archived
+            .iter()
+            .map(|record| record.instance.address.clone())
+            .collect::<HashSet<_>>();
+
+        let purged = {
+            let mut archive = ARCHIVE.get().unwrap().lock().unwrap();
+            archive
+                .archived
+                .extend(archived.iter().map(|record| ArchivedRecord {
+                    instance: record.instance.clone(),
+                    dead_since: record.dead_since,
+                    archived_on: now,
+                }));
+
+            match config.delete_after {
+                Some(after) => archive.purge_before(
+                    now - Duration::seconds(after as i64),
+                    now,
+                    config.remember,
+                ),
+                None => Vec::new(),
+            }
+        };
+
+        if archived.is_empty() && purged.is_empty() {
+            return;
+        }
+
+        // the archive is saved before the instances leave the list, so a failed save or a crash
+        // in between loses nothing
+        if let Err(e) = Self::save_latest(|| ARCHIVE.get().unwrap().lock().unwrap().clone()).await {
+            error!("Could not save {}: {e}", Self::path().display());
+            ARCHIVE
+                .get()
+                .unwrap()
+                .lock()
+                .unwrap()
+                .archived
+                .retain(|record| !addresses.contains(&record.instance.address));
+            return;
+        }
+
+        let taken = match cutoff {
+            Some(cutoff) if !archived.is_empty() => INSTANCES_RECORD
+                .get()
+                .unwrap()
+                .lock()
+                .unwrap()
+                .take_dead_before(cutoff),
+            _ => Vec::new(),
+        };
+
+        // revived while the archive was being saved, they stay listed
+        if taken.len() != archived.len() {
+            let taken = taken
+                .iter()
+                .map(|record| &record.instance.address)
+                .collect::<HashSet<_>>();
+            ARCHIVE
+                .get()
+                .unwrap()
+                .lock()
+                .unwrap()
+                .archived
+                .retain(|record| {
+                    !addresses.contains(&record.instance.address)
+                        || taken.contains(&record.instance.address)
+                });
+            if let Err(e) =
+                Self::save_latest(|| ARCHIVE.get().unwrap().lock().unwrap().clone()).await
+            {
+                error!("Could not save {}: {e}", Self::path().display());
+            }
+        }
+
+        if !taken.is_empty() {
+            {
+                let mut polled = POLLING_RECORD.get().unwrap().lock().unwrap();
+                let mut history = POLLING_HISTORY.get().unwrap().lock().unwrap();
+                for record in taken.iter() {
+                    polled.0.remove(&record.instance.address);
+                    history.0.remove(&record.instance.address);
+                }
+            }
+
+            let stats = INSTANCES_RECORD.get().unwrap().lock().unwrap().stat();
+            *INSTANCES_STATS.get().unwrap().lock().unwrap() = stats;
+            let _ = STORAGE.get().unwrap().save_instances().await;
+            let _ = STORAGE.get().unwrap().save_polling().await;
+        }
+
+        info!(
+            "Archived {} and deleted {} long dead instances",
+            taken.len(),
+            purged.len()
+        );
+    }
+
+    /// Deletes archived instances that have been dead since before `cutoff`, keeping their
+    /// addresses under `purged` if `remember` is set.
+    fn purge_before(
+        &mut self,
+        cutoff: DateTime<Utc>,
+        now: DateTime<Utc>,
+        remember: bool,
+    ) -> Vec<PurgedRecord> {
+        let mut purged = Vec::new();
+
+        self.archived.retain(|record| {
+            let keep = record.dead_since >= cutoff;
+            if !keep {
+                purged.push(PurgedRecord {
+                    address: record.instance.address.clone(),
+                    region: record.instance.region.clone(),
+                    dead_since: record.dead_since,
+                    purged_on: now,
+                });
+            }
+            keep
+        });
+
+        if remember {
+            self.purged.extend(purged.iter().cloned());
+        }
+
+        purged
+    }
+
+    /// Drops `address` from the archive as it is submitted again, returns a note on its history
+    /// if it was there.
+    pub async fn forget(address: &str) -> Option<String> {
+        let note = {
+            let mut archive = ARCHIVE.get().unwrap().lock().unwrap();
+
+            let archived = archive
+                .archived
+                .iter()
+                .position(|record| record.instance.address == address)
+                .map(|i| archive.archived.remove(i));
+            let purged = archive
+                .purged
+                .iter()
+                .position(|record| record.address == address)
+                .map(|i| archive.purged.remove(i));
+
+            let note = match (archived, purged) {
+                (Some(record), _) => format!(
+                    "listed before, archived on {} after being dead since {}",
+                    record.archived_on.format("%Y-%m-%d"),
+                    record.dead_since.format("%Y-%m-%d")
+                ),
+                (None, Some(record)) => format!(
+                    "listed before, removed on {} after being dead since {}",
+                    record.purged_on.format("%Y-%m-%d"),
+                    record.dead_since.format("%Y-%m-%d")
+                ),
+                (None, None) => return None,
+            };
+
+            note
+        };
+
+        let _ = Self::save_latest(|| ARCHIVE.get().unwrap().lock().unwrap().clone()).await;
+        Some(note)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn archived(address: &str, dead_since: DateTime<Utc>) -> ArchivedRecord {
+        ArchivedRecord {
+            instance: InstanceRecord {
+                address: address.to_string(),
+                region: "eu".to_string(),
+                backer: Vec::new(),
+                streak: Streak::default(),
+            },
+            dead_since,
+            archived_on: dead_since,
+        }
+    }
+
+    fn archive(cutoff: DateTime<Utc>) -> Archive {
+        Archive {
+            archived: vec![
+                archived("before.example", cutoff - Duration::seconds(1)),
+                archived("at.example", cutoff),
+                archived("after.example", cutoff + Duration::seconds(1)),
+            ],
+            purged: Vec::new(),
+        }
+    }
+
+    fn addresses<'a>(addresses: impl Iterator<Item = &'a String>) -> Vec<&'a str> {
+        addresses.map(String::as_str).collect()
+    }
+
+    #[test]
+    fn purge_before_cutoff() {
+        let now = Utc::now();
+        let cutoff = now - Duration::days(180);
+        let mut archive = archive(cutoff);
+
+        let purged = archive.purge_before(cutoff, now, false);
+
+        assert_eq!(
+            addresses(purged.iter().map(|record| &record.address)),
+            ["before.example"]
+        );
+        assert_eq!(purged[0].purged_on, now);
+        // dead since exactly the cutoff is kept
+        assert_eq!(
+            addresses(
+                archive
+                    .archived
+                    .iter()
+                    .map(|record| &record.instance.address)
+            ),
+            ["at.example", "after.example"]
+        );
+    }
+
+    #[test]
+    fn purge_before_remember() {
+        let now = Utc::now();
+        let cutoff = now - Duration::days(180);
+
+        for remember in [false, true] {
+            let mut archive = archive(cutoff);
+            let purged = archive.purge_before(cutoff, now, remember);
+
+            assert_eq!(purged.len(), 1);
+            assert_eq!(
+                addresses(archive.purged.iter().map(|record| &record.address)),
+                if remember {
+                    vec!["before.example"]
+                } else {
+                    Vec::new()
+                },
+                "remember {remember}"
+            );
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use std::error::Error;
+
+use async_trait::async_trait;
+
+use crate::*;
+
+/// Persists `INSTANCES_RECORD`, `POLLING_RECORD` and `POLLING_HISTORY`.
+///
+/// The records are always worked on in memory, a backend only decides how the changes reach
+/// the disk.
+#[async_trait]
+pub trait Storage: Send + Sync {
+    async fn load(
+        &self,
+    ) -> Result<(InstancesRecords, PollingRecord, PollingHistory), Box<dyn Error>>;
+
+    /// Saves the current `INSTANCES_RECORD`.
+    async fn save_instances(&self) -> Result<(), Box<dyn Error>>;
+
+    /// Saves the current `POLLING_RECORD` and `POLLING_HISTORY`.
+    async fn save_polling(&self) -> Result<(), Box<dyn Error>>;
+
+    /// Called after every poll, once `record` has been added to `POLLING_RECORD` and
+    /// `POLLING_HISTORY`.
+    async fn record_poll(
+        &self,
+        instance: &str,
+        record: &PolledSingleRecord,
+    ) -> Result<(), Box<dyn Error>>;
+}
+
+impl StorageBackend {
+    pub async fn open(self) -> Result<Box<dyn Storage>, Box<dyn Error>> {
+        Ok(match self {
+            Self::Json => Box::new(JsonStorage),
+            Self::Sqlite => {
+                Box::new(SqliteStorage::open(Paths::get().data.join("vidiup.db")).await?)
+            }
+        })
+    }
+}
+
+/// Each record is its own [`SavedFile`], rewritten in full on every save. Polls are only
+/// written out with the rest of the polling records.
+pub struct JsonStorage;
+
+#[async_trait]
+impl Storage for JsonStorage {
+    async fn load(
+        &self,
+    ) -> Result<(InstancesRecords, PollingRecord, PollingHistory), Box<dyn Error>> {
+        let instances = InstancesRecords::load_or_create().await?;
+        let polled = PollingRecord::load_or_create().await?;
+        let history = PollingHistory::load().await.unwrap_or_default();
+
+        Ok((instances, polled, history))
+    }
+
+    async fn save_instances(&self) -> Result<(), Box<dyn Error>> {
+        InstancesRecords::save_latest(|| INSTANCES_RECORD.get().unwrap().lock().unwrap().clone())
+            .await
+    }
+
+    async fn save_polling(&self) -> Result<(), Box<dyn Error>> {
+        PollingRecord::save_latest(|| POLLING_RECORD.get().unwrap().lock().unwrap().clone())
+            .await?;
+        PollingHistory::save_latest(|| POLLING_HISTORY.get().unwrap().lock().unwrap().clone()).await
+    }
+
+    async fn record_poll(&self, _: &str, _: &PolledSingleRecord) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+}
@@ -0,0 +1,327 @@
+use std::collections::{HashMap, VecDeque};
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Directory, Outcome, PolledSingleRecord, PollingFeaturesConfig, SavedFile, OUTBOUND_CONFIG,
+};
+
+pub const DAY: u64 = 24 * 60 * 60;
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct PollingHistory(pub HashMap<String, InstanceHistory>);
+
+impl SavedFile for PollingHistory {
+    const DIR: Directory = Directory::Data;
+    const PATH: &'static str = "pollinghistory.json";
+}
+
+impl PollingHistory {
+    pub fn push(&mut self, instance: &str, record: PolledSingleRecord) {
+        let size = OUTBOUND_CONFIG.get().unwrap().history.size;
+        self.0
+            .entry(instance.to_string())
+            .or_default()
+            .push(record, size);
+    }
+
+    pub fn get(&self, instance: &str) -> Option<&InstanceHistory> {
+        self.0.get(instance)
+    }
+}
+
+/// Most recent polls of a single instance, oldest first. Only the outcomes, which features were
+/// expected and when each poll happened are kept, see [`PolledSingleRecord::without_samples`].
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(from = "VecDeque<PolledSingleRecord>")]
+pub struct InstanceHistory(pub VecDeque<PolledSingleRecord>);
+
+impl From<VecDeque<PolledSingleRecord>> for InstanceHistory {
+    fn from(records: VecDeque<PolledSingleRecord>) -> Self {
+        Self(
+            records
+                .into_iter()
+                .map(PolledSingleRecord::without_samples)
+                .collect(),
+        )
+    }
+}
+
+impl InstanceHistory {
+    pub fn push(&mut self, record: PolledSingleRecord, size: usize) {
+        self.0.push_back(record.without_samples());
+
+        while self.0.len() > size {
+            self.0.pop_front();
+        }
+    }
+
+    fn since(&self, secs: u64) -> impl Iterator<Item = &PolledSingleRecord> {
+        let from = (Utc::now().timestamp() as u64).saturating_sub(secs);
+        self.0
+            .iter()
+            .filter(move |record| record.last_polled >= from)
+    }
+
+    /// Fraction of polls within the last `secs` seconds where the instance was well.
+    pub fn uptime(&self, secs: u64) -> Option<f64> {
+        let (total, well) = self.since(secs).fold((0, 0), |(total, well), record| {
+            (total + 1, well + record.well() as u32)
+        });
+
+        (total != 0).then(|| well as f64 / total as f64)
+    }
+
+    /// Like [`Self::uptime`], but `None` if polls within the window were dropped to keep the
+    /// history at `size`, so a window is never quietly shortened to what is left.
+    fn covered_uptime(&self, secs: u64, size: usize) -> Option<f64> {
+        let from = (Utc::now().timestamp() as u64).saturating_sub(secs);
+        let trimmed = self.0.len() >= size
+            && self
+                .0
+                .front()
+                .is_some_and(|oldest| oldest.last_polled > from);
+
+        if trimmed {
+            None
+        } else {
+            self.uptime(secs)
+        }
+    }
+
+    /// Latency of `feature` at percentile `p` (0 to 1), counting successful polls only.
+    pub fn percentile(&self, feature: Feature, p: f64) -> Option<u32> {
+        let mut latencies = self
+            .0
+            .iter()
+            .filter_map(|record| feature.get(record))
+            .collect::<Vec<_>>();
+
+        if latencies.is_empty() {
+            return None;
+        }
+
+        latencies.sort_unstable();
+        let index = ((latencies.len() - 1) as f64 * p).round() as usize;
+        Some(latencies[index])
+    }
+
+    /// Median total score over the polls where every expected feature succeeded.
+    pub fn median_score(&self) -> Option<u32> {
+        let mut scores = self
+            .0
+            .iter()
+            .filter(|record| record.complete())
+            .map(PolledSingleRecord::score)
+            .filter(|score| *score != 0)
+            .collect::<Vec<_>>();
+
+        if scores.is_empty() {
+            return None;
+        }
+
+        scores.sort_unstable();
+        Some(scores[scores.len() / 2])
+    }
+
+    /// Times the health of consecutive polls changed, oldest first. These are not bucket moves,
+    /// which also depend on `hysteresis` and the thresholds.
+    pub fn health_changes(&self) -> Vec<HealthChange> {
+        let mut changes = Vec::new();
+        let mut records = self.0.iter();
+
+        let Some(mut last) = records.next().map(Health::of) else {
+            return changes;
+        };
+
+        for record in records {
+            let health = Health::of(record);
+
+            if health != last {
+                changes.push(HealthChange {
+                    at: record.last_polled,
+                    from: last,
+                    to: health,
+                });
+                last = health;
+            }
+        }
+
+        changes
+    }
+
+    pub fn summary(&self) -> HistorySummary {
+        let size = OUTBOUND_CONFIG.get().unwrap().history.size;
+
+        HistorySummary {
+            uptime: Uptime {
+                day: self.covered_uptime(DAY, size),
+                week: self.covered_uptime(7 * DAY, size),
+                month: self.covered_uptime(30 * DAY, size),
+            },
+            video: LatencySummary::new(self, Feature::Video),
+            playlist: LatencySummary::new(self, Feature::Playlist),
+            channel: LatencySummary::new(self, Feature::Channel),
+            search: LatencySummary::new(self, Feature::Search),
+            playback: LatencySummary::new(self, Feature::Playback),
+            comments: LatencySummary::new(self, Feature::Comments),
+            captions: LatencySummary::new(self, Feature::Captions),
+            trending: LatencySummary::new(self, Feature::Trending),
+            popular: LatencySummary::new(self, Feature::Popular),
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum Health {
+    /// Scored at least `health.thresholds.hot`, with every required feature working.
+    Up,
+    /// Scored in between.
+    Degraded,
+    /// Scored `health.thresholds.dead` or below.
+    Down,
+}
+
+impl Health {
+    pub fn of(record: &PolledSingleRecord) -> Self {
+        if record.well() {
+            Self::Up
+        } else if record.dead() {
+            Self::Down
+        } else {
+            Self::Degraded
+        }
+    }
+}
+
+#[derive(Serialize, Clone)]
+pub struct HealthChange {
+    pub at: u64,
+    pub from: Health,
+    pub to: Health,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum Feature {
+    Video,
+    Playlist,
+    Channel,
+    Search,
+    Playback,
+    Comments,
+    Captions,
+    Trending,
+    Popular,
+}
+
+impl Feature {
+    pub const ALL: [Self; 9] = [
+        Self::Video,
+        Self::Playlist,
+        Self::Channel,
+        Self::Search,
+        Self::Playback,
+        Self::Comments,
+        Self::Captions,
+        Self::Trending,
+        Self::Popular,
+    ];
+
+    pub fn enabled(&self, features: &PollingFeaturesConfig) -> bool {
+        match self {
+            Self::Video => features.video,
+            Self::Playlist => features.playlist,
+            Self::Channel => features.channel,
+            Self::Search => features.search,
+            Self::Playback => features.playback,
+            Self::Comments => features.comments,
+            Self::Captions => features.captions,
+            Self::Trending => features.trending,
+            Self::Popular => features.popular,
+        }
+    }
+
+    pub fn outcome<'a>(&self, record: &'a PolledSingleRecord) -> Option<&'a Outcome> {
+        match self {
+            Self::Video => &record.video,
+            Self::Playlist => &record.playlist,
+            Self::Channel => &record.channel,
+            Self::Search => &record.search,
+            Self::Playback => &record.playback,
+            Self::Comments => &record.comments,
+            Self::Captions => &record.captions,
+            Self::Trending => &record.trending,
+            Self::Popular => &record.popular,
+        }
+        .as_ref()
+    }
+
+    /// Latency of the feature if it succeeded.
+    pub fn get(&self, record: &PolledSingleRecord) -> Option<u32> {
+        self.outcome(record).and_then(Outcome::latency)
+    }
+}
+
+impl From<&[Feature]> for PollingFeaturesConfig {
+    fn from(features: &[Feature]) -> Self {
+        Self {
+            video: features.contains(&Feature::Video),
+            playlist: features.contains(&Feature::Playlist),
+            search: features.contains(&Feature::Search),
+            channel: features.contains(&Feature::Channel),
+            playback: features.contains(&Feature::Playback),
+            comments: features.contains(&Feature::Comments),
+            captions: features.contains(&Feature::Captions),
+            trending: features.contains(&Feature::Trending),
+            popular: features.contains(&Feature::Popular),
+        }
+    }
+}
+
+#[derive(Serialize, Clone)]
+pub struct HistorySummary {
+    pub uptime: Uptime,
+    pub video: LatencySummary,
+    pub playlist: LatencySummary,
+    pub channel: LatencySummary,
+    pub search: LatencySummary,
+    pub playback: LatencySummary,
+    pub comments: LatencySummary,
+    pub captions: LatencySummary,
+    pub trending: LatencySummary,
+    pub popular: LatencySummary,
+}
+
+#[derive(Serialize, Clone)]
+pub struct Uptime {
+    pub day: Option<f64>,
+    pub week: Option<f64>,
+    pub month: Option<f64>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct LatencySummary {
+    pub p50: Option<u32>,
+    pub p95: Option<u32>,
+}
+
+impl LatencySummary {
+    pub fn new(history: &InstanceHistory, feature: Feature) -> Self {
+        Self {
+            p50: history.percentile(feature, 0.5),
+            p95: history.percentile(feature, 0.95),
+        }
+    }
+
+    pub fn offset(&mut self, offset: i32) {
+        self.p50 = self
+            .p50
+            .map(|latency| latency.saturating_add_signed(offset));
+        self.p95 = self
+            .p95
+            .map(|latency| latency.saturating_add_signed(offset));
+    }
+}
@@ -1,16 +1,17 @@
 use std::collections::HashMap;
 
 use chrono::{DateTime, Utc};
-use log::warn;
+use log::{info, warn};
 use serde::{Deserialize, Serialize};
 
 use crate::*;
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Default)]
 pub struct InstancesRecords(pub HashMap<String, RegionRecords>);
 
 impl SavedFile for InstancesRecords {
-    const PATH: &'static str = ".local/share/vidiup/instances.json";
+    const DIR: Directory = Directory::Data;
+    const PATH: &'static str = "instances.json";
 }
 
 impl InstancesRecords {
@@ -57,18 +58,116 @@ impl InstancesRecords {
         }
     }
 
-    pub fn add(&mut self, instance: String, region: String, backer: String) {
+    pub fn remove(&mut self, instance: &str) -> bool {
+        let mut removed = false;
+
+        for region in self.0.values_mut() {
+            removed |= region.remove(instance);
+        }
+
+        removed
+    }
+
+    /// Every instance that has been dead since before `cutoff`.
+    pub fn dead_before(&self, cutoff: DateTime<Utc>) -> Vec<DeadRecord> {
+        self.0
+            .values()
+            .flat_map(|region| region.dead_before(cutoff))
+            .collect()
+    }
+
+    /// Removes every instance that has been dead since before `cutoff`.
+    pub fn take_dead_before(&mut self, cutoff: DateTime<Utc>) -> Vec<DeadRecord> {
+        self.0
+            .values_mut()
+            .flat_map(|region| region.take_dead_before(cutoff))
+            .collect()
+    }
+
+    pub fn blacklisted(&self) -> Vec<String> {
+        self.0
+            .values()
+            .flat_map(|region| region.all_instances())
+            .filter(|instance| is_blacklisted(instance))
+            .map(str::to_string)
+            .collect()
+    }
+
+    pub fn region_of_mut(&mut self, instance: &str) -> Option<&mut RegionRecords> {
+        self.0.values_mut().find(|region| region.contains(instance))
+    }
+
+    /// Moves the instance to another region, keeping it in the same bucket.
+    pub fn set_region(&mut self, instance: &str, region: &str) -> bool {
+        let mut moved = match self.region_of_mut(instance) {
+            Some(records) => records.split_off(instance),
+            None => return false,
+        };
+
+        moved.set_region(region);
+        self.0.entry(region.to_string()).or_default().append(moved);
+        true
+    }
+
+    pub fn contains(&self, instance: &str) -> bool {
+        self.0.values().any(|region| region.contains(instance))
+    }
+
+    /// Unblacklisted entries of `bucket` in `region`, or in every region if `None`.
+    ///
+    /// A `detected` region is only a guess at what suits the client, so if it has none every
+    /// region is used instead.
+    pub fn candidates<'a>(
+        &'a self,
+        region: Option<&str>,
+        detected: bool,
+        bucket: fn(&RegionRecords) -> &Vec<HotRecord>,
+    ) -> Vec<&'a HotRecord> {
+        let from = |regions: Vec<&'a RegionRecords>| {
+            regions
+                .into_iter()
+                .flat_map(bucket)
+                .filter(|entry| !is_blacklisted(&entry.instance.address))
+                .collect::<Vec<_>>()
+        };
+
+        let Some(region) = region else {
+            return from(self.0.values().collect());
+        };
+
+        let candidates = from(self.0.get(region).into_iter().collect());
+        if candidates.is_empty() && detected {
+            from(self.0.values().collect())
+        } else {
+            candidates
+        }
+    }
+
+    pub fn pending(&self) -> usize {
+        self.0.values().map(|region| region.pending.len()).sum()
+    }
+
+    /// Number of instances `ip` is a backer of, not counting `except`.
+    pub fn backed_by(&self, ip: &str, except: &str) -> usize {
+        self.0
+            .values()
+            .map(|region| region.backed_by(ip, except))
+            .sum()
+    }
+
+    /// Adds `backer` to the instance if it is listed, otherwise adds it to `pending`. Returns
+    /// whether it was added as new.
+    pub fn add(&mut self, instance: String, region: String, backer: String) -> bool {
         for (region_current, records) in self.0.iter_mut() {
             if records.add_backer(&instance, &backer) {
                 if region != region_current.as_str() {
                     warn!("{instance} may be in {region} (currently in {region_current})");
                 } else {
-                    let record = self.clone();
-                    tokio::spawn(async move {
-                        let _ = record.save().await;
+                    tokio::spawn(async {
+                        let _ = STORAGE.get().unwrap().save_instances().await;
                     });
                 }
-                return;
+                return false;
             }
         }
 
@@ -80,14 +179,44 @@ impl InstancesRecords {
                 address: instance,
                 region,
                 backer: vec![backer],
+                streak: Streak::default(),
             });
 
-        let record = self.clone();
-        tokio::spawn(async move {
-            let _ = record.save().await;
-            INSTANCES_STATS.get().unwrap().lock().unwrap().3 += 1;
+        INSTANCES_STATS.get().unwrap().lock().unwrap().3 += 1;
+        tokio::spawn(async {
+            let _ = STORAGE.get().unwrap().save_instances().await;
         });
+        true
+    }
+}
+
+/// Removes the instances from every bucket of every region along with their polling records and
+/// history, then refreshes the stats and saves. Returns whether any of them were found.
+pub async fn remove_instances(instances: &[String]) -> bool {
+    let removed = {
+        let mut records = INSTANCES_RECORD.get().unwrap().lock().unwrap();
+        let mut polled = POLLING_RECORD.get().unwrap().lock().unwrap();
+        let mut history = POLLING_HISTORY.get().unwrap().lock().unwrap();
+
+        let mut removed = false;
+        for instance in instances {
+            removed |= records.remove(instance);
+            polled.0.remove(instance);
+            history.0.remove(instance);
+        }
+
+        if removed {
+            *INSTANCES_STATS.get().unwrap().lock().unwrap() = records.stat();
+        }
+        removed
+    };
+
+    if removed {
+        let _ = STORAGE.get().unwrap().save_instances().await;
+        let _ = STORAGE.get().unwrap().save_polling().await;
     }
+
+    removed
 }
 
 #[derive(Serialize, Deserialize, Clone, Default)]
@@ -135,6 +264,184 @@ impl RegionRecords {
         }
     }
 
+    pub fn remove(&mut self, instance: &str) -> bool {
+        let before = self.all_instances().len();
+
+        self.hot.retain(|entry| entry.instance.address != instance);
+        self.recovered
+            .retain(|entry| entry.instance.address != instance);
+        self.recovering
+            .retain(|entry| entry.instance.address != instance);
+        self.dead
+            .retain(|entry| entry.0.instance.address != instance);
+        self.stashed_recovering
+            .retain(|entry| entry.address != instance);
+        self.stashed_dead
+            .retain(|entry| entry.instance.address != instance);
+        self.stashed.retain(|entry| entry.address != instance);
+        self.pending.retain(|entry| entry.address != instance);
+
+        before != self.all_instances().len()
+    }
+
+    /// Dead and stashed dead instances that have been dead since before `cutoff`.
+    pub fn dead_before(&self, cutoff: DateTime<Utc>) -> Vec<DeadRecord> {
+        self.dead
+            .iter()
+            .map(|entry| &entry.0)
+            .chain(self.stashed_dead.iter())
+            .filter(|entry| entry.dead_since < cutoff)
+            .cloned()
+            .collect()
+    }
+
+    /// Removes dead and stashed dead instances that have been dead since before `cutoff`.
+    pub fn take_dead_before(&mut self, cutoff: DateTime<Utc>) -> Vec<DeadRecord> {
+        let mut taken = Vec::new();
+
+        self.dead.retain(|entry| {
+            let keep = entry.0.dead_since >= cutoff;
+            if !keep {
+                taken.push(entry.0.clone());
+            }
+            keep
+        });
+        self.stashed_dead.retain(|entry| {
+            let keep = entry.dead_since >= cutoff;
+            if !keep {
+                taken.push(entry.clone());
+            }
+            keep
+        });
+
+        taken
+    }
+
+    /// Name of the bucket the instance is in.
+    pub fn bucket(&self, instance: &str) -> Option<&'static str> {
+        let hot = |records: &[HotRecord]| {
+            records
+                .iter()
+                .any(|record| record.instance.address == instance)
+        };
+        let plain =
+            |records: &[InstanceRecord]| records.iter().any(|record| record.address == instance);
+
+        if hot(&self.hot) {
+            Some("hot")
+        } else if hot(&self.recovered) {
+            Some("recovered")
+        } else if hot(&self.recovering) {
+            Some("recovering")
+        } else if self
+            .dead
+            .iter()
+            .any(|record| record.0.instance.address == instance)
+        {
+            Some("dead")
+        } else if plain(&self.stashed_recovering) {
+            Some("stashedRecovering")
+        } else if self
+            .stashed_dead
+            .iter()
+            .any(|record| record.instance.address == instance)
+        {
+            Some("stashedDead")
+        } else if plain(&self.stashed) {
+            Some("stashed")
+        } else if plain(&self.pending) {
+            Some("pending")
+        } else {
+            None
+        }
+    }
+
+    /// Removes the instance, returning it in a region of its own, in the same bucket.
+    pub fn split_off(&mut self, instance: &str) -> RegionRecords {
+        fn take<T>(records: &mut Vec<T>, matches: impl Fn(&T) -> bool) -> Vec<T> {
+            let (taken, kept) = std::mem::take(records).into_iter().partition(matches);
+            *records = kept;
+            taken
+        }
+
+        RegionRecords {
+            hot: take(&mut self.hot, |entry| entry.instance.address == instance),
+            recovered: take(&mut self.recovered, |entry| {
+                entry.instance.address == instance
+            }),
+            recovering: take(&mut self.recovering, |entry| {
+                entry.instance.address == instance
+            }),
+            dead: take(&mut self.dead, |entry| entry.0.instance.address == instance),
+            stashed_recovering: take(&mut self.stashed_recovering, |entry| {
+                entry.address == instance
+            }),
+            stashed_dead: take(&mut self.stashed_dead, |entry| {
+                entry.instance.address == instance
+            }),
+            stashed: take(&mut self.stashed, |entry| entry.address == instance),
+            pending: take(&mut self.pending, |entry| entry.address == instance),
+        }
+    }
+
+    pub fn append(&mut self, mut other: RegionRecords) {
+        self.hot.append(&mut other.hot);
+        self.recovered.append(&mut other.recovered);
+        self.recovering.append(&mut other.recovering);
+        self.dead.append(&mut other.dead);
+        self.stashed_recovering
+            .append(&mut other.stashed_recovering);
+        self.stashed_dead.append(&mut other.stashed_dead);
+        self.stashed.append(&mut other.stashed);
+        self.pending.append(&mut other.pending);
+    }
+
+    pub fn set_region(&mut self, region: &str) {
+        self.hot
+            .iter_mut()
+            .chain(self.recovered.iter_mut())
+            .chain(self.recovering.iter_mut())
+            .map(|entry| &mut entry.instance)
+            .chain(self.dead.iter_mut().map(|entry| &mut entry.0.instance))
+            .chain(
+                self.stashed_dead
+                    .iter_mut()
+                    .map(|entry| &mut entry.instance),
+            )
+            .chain(
+                self.stashed_recovering
+                    .iter_mut()
+                    .chain(self.stashed.iter_mut())
+                    .chain(self.pending.iter_mut()),
+            )
+            .for_each(|entry| entry.region = region.to_string());
+    }
+
+    pub fn reset_weight(&mut self, instance: &str) -> bool {
+        if let Some(hotrecord) = self
+            .hot
+            .iter_mut()
+            .chain(self.recovered.iter_mut())
+            .chain(self.recovering.iter_mut())
+            .find(|record| record.instance.address == instance)
+        {
+            hotrecord.weight = 1.;
+            hotrecord.latency = 0.;
+            return true;
+        }
+
+        if let Some(deadrecord) = self
+            .dead
+            .iter_mut()
+            .find(|record| record.0.instance.address == instance)
+        {
+            deadrecord.1 = 1.;
+            return true;
+        }
+
+        false
+    }
+
     pub fn update_weight(&mut self, instance: &str, multiplier: f64) -> bool {
         if let Some(hotrecord) = self
             .hot
@@ -264,6 +571,7 @@ impl RegionRecords {
             self.recovering.push(HotRecord {
                 instance: removed.0.instance,
                 weight: removed.1,
+                latency: 0.,
             });
             return true;
         }
@@ -309,6 +617,7 @@ impl RegionRecords {
             self.recovered.push(HotRecord {
                 instance: removed.0.instance,
                 weight: removed.1,
+                latency: 0.,
             });
             return true;
         }
@@ -353,6 +662,20 @@ impl RegionRecords {
         false
     }
 
+    /// Number of instances in each bucket, named as in [`RegionRecords::bucket`].
+    pub fn counts(&self) -> [(&'static str, usize); 8] {
+        [
+            ("hot", self.hot.len()),
+            ("recovered", self.recovered.len()),
+            ("recovering", self.recovering.len()),
+            ("dead", self.dead.len()),
+            ("stashedRecovering", self.stashed_recovering.len()),
+            ("stashedDead", self.stashed_dead.len()),
+            ("stashed", self.stashed.len()),
+            ("pending", self.pending.len()),
+        ]
+    }
+
     pub fn stat(&self) -> (usize, usize, usize, usize) {
         (
             self.hot.len() + self.recovered.len() + self.stashed.len(),
@@ -362,6 +685,11 @@ impl RegionRecords {
         )
     }
 
+    /// Whether the instance is in `dead` or `stashedDead`.
+    fn is_dead(&self, instance: &str) -> bool {
+        matches!(self.bucket(instance), Some("dead" | "stashedDead"))
+    }
+
     pub fn update_single(&mut self, instance: &str, record: PolledSingleRecord) -> bool {
         let mainconfig = MASTER_CONFIG.get().unwrap();
 
@@ -369,16 +697,47 @@ impl RegionRecords {
             return false;
         }
 
-        if record.well() {
-            self.revive(instance)
-        } else if record.dead() {
-            self.kill(instance)
-        } else {
-            self.rest(instance)
+        let reliable = POLLING_HISTORY
+            .get()
+            .unwrap()
+            .lock()
+            .unwrap()
+            .get(instance)
+            .and_then(|history| history.uptime(DAY))
+            .is_some_and(|uptime| uptime >= OUTBOUND_CONFIG.get().unwrap().history.flaky_uptime);
+
+        let hysteresis = &OUTBOUND_CONFIG.get().unwrap().hysteresis;
+        let well = record.well();
+        let streak = {
+            let entry = self.record_mut(instance).unwrap();
+            entry.streak.push(well);
+            entry.streak
         };
 
-        // i will do standard deviation stuff so that instances that are too slow will be
-        // put in recovering, but not now i cba
+        // only move once the instance has been well, or not, for enough polls in a row
+        if well {
+            if streak.successes >= hysteresis.successes {
+                self.revive(instance);
+            }
+        } else if streak.failures >= hysteresis.failures {
+            if record.dead() && !reliable && !record.blocked() {
+                self.kill(instance);
+            } else if !self.is_dead(instance)
+                || record.health_score()
+                    >= OUTBOUND_CONFIG.get().unwrap().health.thresholds.recovering
+            {
+                // anything alive that is not well rests, but a dead instance is only brought
+                // back to recovering once it scores at least the `recovering` threshold
+                self.rest(instance);
+            }
+        }
+
+        // a poll with failures only adds up the latencies that answered, which would make a
+        // broken instance look faster
+        if record.complete() && record.score() != 0 {
+            self.reweight(instance, record.score());
+            self.demote_outlier(instance, record.score());
+        }
 
         if self.hot.len() < mainconfig.hot_per_region as usize {
             if let Some(instance) = self.recovered.pop() {
@@ -387,6 +746,7 @@ impl RegionRecords {
                 self.hot.push(HotRecord {
                     instance,
                     weight: 1.,
+                    latency: 0.,
                 })
             }
         }
@@ -394,6 +754,124 @@ impl RegionRecords {
         true
     }
 
+    /// Moves the instance to `recovering` if its score is more than `demotion.sigmas` standard
+    /// deviations slower than the mean of the region's hot and recovered instances.
+    ///
+    /// Scores are the median of each instance's poll history where there is one, so a single
+    /// slow poll does not demote an otherwise fast instance.
+    ///
+    /// Called with `INSTANCES_RECORD` held, and locks `POLLING_RECORD` and `POLLING_HISTORY` in
+    /// turn, see the lock order in `values.rs`.
+    pub fn demote_outlier(&mut self, instance: &str, score: u32) -> bool {
+        let config = &OUTBOUND_CONFIG.get().unwrap().demotion;
+
+        if score == 0 {
+            return false;
+        }
+
+        let (score, scores) = {
+            let polled = POLLING_RECORD.get().unwrap().lock().unwrap();
+            let history = POLLING_HISTORY.get().unwrap().lock().unwrap();
+            let score_of = |address: &str| {
+                history
+                    .get(address)
+                    .and_then(InstanceHistory::median_score)
+                    .or_else(|| {
+                        if address == instance {
+                            Some(score)
+                        } else {
+                            polled
+                                .0
+                                .get(address)
+                                .filter(|record| record.complete())
+                                .map(PolledSingleRecord::score)
+                        }
+                    })
+            };
+
+            let scores = self
+                .hot
+                .iter()
+                .chain(self.recovered.iter())
+                .filter_map(|record| score_of(&record.instance.address))
+                .filter(|score| *score != 0)
+                .map(|score| score as f64)
+                .collect::<Vec<_>>();
+
+            (score_of(instance).unwrap_or(score), scores)
+        };
+
+        if scores.len() < config.min_samples as usize {
+            return false;
+        }
+
+        let mean = scores.iter().sum::<f64>() / scores.len() as f64;
+        let deviation = (scores
+            .iter()
+            .map(|score| (score - mean).powi(2))
+            .sum::<f64>()
+            / scores.len() as f64)
+            .sqrt();
+        let limit = mean + config.sigmas * deviation;
+
+        if deviation == 0. || (score as f64) <= limit {
+            return false;
+        }
+
+        if self.rest(instance) {
+            info!(
+                "Demoted {instance} to recovering: score {score}ms, mean {mean:.0}ms, deviation {deviation:.0}ms, limit {limit:.0}ms ({} sigmas, {} samples)",
+                config.sigmas,
+                scores.len()
+            );
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Folds a new score into the instance's latency history, then recomputes the weight of
+    /// every hot instance relative to the region's average.
+    pub fn reweight(&mut self, instance: &str, score: u32) {
+        let config = &OUTBOUND_CONFIG.get().unwrap().weighting;
+
+        if let Some(hotrecord) = self
+            .hot
+            .iter_mut()
+            .chain(self.recovered.iter_mut())
+            .find(|record| record.instance.address == instance)
+        {
+            hotrecord.update_latency(score, config.smoothing);
+        }
+
+        let polled = self
+            .hot
+            .iter()
+            .filter(|record| record.latency > 0.)
+            .map(|record| record.latency)
+            .collect::<Vec<_>>();
+
+        if polled.is_empty() {
+            return;
+        }
+
+        let mean = polled.iter().sum::<f64>() / polled.len() as f64;
+
+        let targets = self
+            .hot
+            .iter()
+            .filter(|record| record.latency > 0.)
+            .map(|record| {
+                let target = (mean / record.latency).clamp(config.min, config.max);
+                (record.instance.address.clone(), target / record.weight)
+            })
+            .collect::<Vec<_>>();
+
+        for (address, multiplier) in targets {
+            self.update_weight(&address, multiplier);
+        }
+    }
+
     pub fn contains(&self, instance: &str) -> bool {
         self.hot
             .iter()
@@ -419,6 +897,49 @@ impl RegionRecords {
             .any(|item| item == instance)
     }
 
+    pub fn backed_by(&self, ip: &str, except: &str) -> usize {
+        self.all_records()
+            .into_iter()
+            .filter(|entry| entry.address != except && entry.backer.iter().any(|b| b == ip))
+            .count()
+    }
+
+    pub fn record_mut(&mut self, instance: &str) -> Option<&mut InstanceRecord> {
+        self.hot
+            .iter_mut()
+            .chain(self.recovered.iter_mut().chain(self.recovering.iter_mut()))
+            .map(|entry| &mut entry.instance)
+            .chain(self.dead.iter_mut().map(|entry| &mut entry.0.instance))
+            .chain(
+                self.stashed_dead
+                    .iter_mut()
+                    .map(|entry| &mut entry.instance),
+            )
+            .chain(
+                self.stashed_recovering
+                    .iter_mut()
+                    .chain(self.stashed.iter_mut())
+                    .chain(self.pending.iter_mut()),
+            )
+            .find(|entry| entry.address == instance)
+    }
+
+    pub fn all_records(&self) -> Vec<&InstanceRecord> {
+        self.hot
+            .iter()
+            .chain(self.recovered.iter().chain(self.recovering.iter()))
+            .map(|entry| &entry.instance)
+            .chain(self.dead.iter().map(|entry| &entry.0.instance))
+            .chain(self.stashed_dead.iter().map(|entry| &entry.instance))
+            .chain(
+                self.stashed_recovering
+                    .iter()
+                    .chain(self.stashed.iter())
+                    .chain(self.pending.iter()),
+            )
+            .collect()
+    }
+
     pub fn all_instances(&self) -> Vec<&str> {
         self.hot
             .iter()
@@ -445,30 +966,178 @@ impl RegionRecords {
     }
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct HotRecord {
     pub instance: InstanceRecord,
     pub weight: f64,
+    /// Smoothed poll score, 0 if the instance has not been polled since it was revived.
+    #[serde(default)]
+    pub latency: f64,
 }
 
 impl HotRecord {
     pub fn update_weight(&mut self, multiplier: f64) {
         self.weight *= multiplier;
     }
+
+    pub fn update_latency(&mut self, score: u32, smoothing: f64) {
+        if self.latency == 0. {
+            self.latency = score as f64;
+        } else {
+            self.latency = smoothing * score as f64 + (1. - smoothing) * self.latency;
+        }
+    }
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct DeadRecord {
     pub instance: InstanceRecord,
     pub dead_since: DateTime<Utc>,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct InstanceRecord {
     pub address: String,
     pub region: String,
     pub backer: Vec<String>,
+    #[serde(default, skip_serializing_if = "Streak::is_empty")]
+    pub streak: Streak,
+}
+
+/// Number of consecutive polls that were well, or that were not, only one is ever non-zero.
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+pub struct Streak {
+    pub successes: u32,
+    pub failures: u32,
+}
+
+impl Streak {
+    pub fn push(&mut self, well: bool) {
+        if well {
+            self.successes = self.successes.saturating_add(1);
+            self.failures = 0;
+        } else {
+            self.failures = self.failures.saturating_add(1);
+            self.successes = 0;
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::*;
+
+    fn dead(address: &str, dead_since: DateTime<Utc>) -> DeadRecord {
+        DeadRecord {
+            instance: InstanceRecord {
+                address: address.to_string(),
+                region: "eu".to_string(),
+                backer: Vec::new(),
+                streak: Streak::default(),
+            },
+            dead_since,
+        }
+    }
+
+    #[test]
+    fn take_dead_before() {
+        let cutoff = Utc::now();
+        let mut records = RegionRecords {
+            dead: vec![
+                (dead("before.example", cutoff - Duration::seconds(1)), 0.),
+                (dead("at.example", cutoff), 0.),
+                (dead("after.example", cutoff + Duration::seconds(1)), 0.),
+            ],
+            stashed_dead: vec![
+                dead("stashed-before.example", cutoff - Duration::days(1)),
+                dead("stashed-after.example", cutoff + Duration::days(1)),
+            ],
+            ..Default::default()
+        };
+
+        let found = records
+            .dead_before(cutoff)
+            .into_iter()
+            .map(|record| record.instance.address)
+            .collect::<Vec<_>>();
+        let taken = records
+            .take_dead_before(cutoff)
+            .into_iter()
+            .map(|record| record.instance.address)
+            .collect::<Vec<_>>();
+        assert_eq!(taken, ["before.example", "stashed-before.example"]);
+        assert_eq!(found, taken);
+
+        // dead since exactly the cutoff is kept
+        assert_eq!(records.bucket("at.example"), Some("dead"));
+        assert_eq!(records.bucket("after.example"), Some("dead"));
+        assert_eq!(records.bucket("stashed-after.example"), Some("stashedDead"));
+        assert_eq!(records.bucket("before.example"), None);
+        assert!(records.take_dead_before(cutoff).is_empty());
+    }
+
+    #[test]
+    fn take_dead_before_every_region() {
+        let cutoff = Utc::now();
+        let old = dead("old.example", cutoff - Duration::days(1));
+        let mut records = InstancesRecords(
+            [("eu", old.clone()), ("us", old)]
+                .into_iter()
+                .map(|(region, record)| {
+                    let records = RegionRecords {
+                        stashed_dead: vec![record],
+                        ..Default::default()
+                    };
+                    (region.to_string(), records)
+                })
+                .collect(),
+        );
+
+        assert_eq!(records.take_dead_before(cutoff).len(), 2);
+        assert!(!records.contains("old.example"));
+    }
+
+    #[test]
+    fn streak_push() {
+        // well, expected successes, expected failures
+        let cases = [
+            (true, 1, 0),
+            (true, 2, 0),
+            (false, 0, 1),
+            (false, 0, 2),
+            (false, 0, 3),
+            (true, 1, 0),
+        ];
+
+        let mut streak = Streak::default();
+        assert!(streak.is_empty());
+
+        for (well, successes, failures) in cases {
+            streak.push(well);
+            assert_eq!((streak.successes, streak.failures), (successes, failures));
+            assert!(!streak.is_empty());
+        }
+    }
+
+    #[test]
+    fn streak_push_saturates() {
+        let mut streak = Streak {
+            successes: u32::MAX,
+            failures: 0,
+        };
+        streak.push(true);
+        assert_eq!(streak.successes, u32::MAX);
+
+        streak.push(false);
+        assert_eq!((streak.successes, streak.failures), (0, 1));
+    }
 }
@@ -1,7 +1,19 @@
+mod archive;
+mod backend;
+mod history;
 mod instances;
 mod polling;
+mod probe;
 mod samples;
+mod sqlite;
+mod status;
 
+pub use archive::*;
+pub use backend::*;
+pub use history::*;
 pub use instances::*;
 pub use polling::*;
+pub use probe::*;
 pub use samples::*;
+pub use sqlite::*;
+pub use status::*;
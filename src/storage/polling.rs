@@ -1,30 +1,32 @@
 use std::{
     collections::{HashMap, HashSet},
     sync::{Arc, Mutex},
-    time::Duration,
 };
 
 use chrono::Utc;
-use invidious::{ClientAsync, ClientAsyncTrait};
+use invidious::ClientAsyncTrait;
+use log::warn;
 use serde::{Deserialize, Serialize};
-use tokio::{
-    task::JoinSet,
-    time::{timeout, Instant},
-};
+use tokio::task::JoinSet;
 
 use crate::{
-    SavedFile, CHANNEL_ID, CONCURRENT_POLLS, INSTANCES_RECORD, INSTANCES_STATS, MASTER_CONFIG,
-    OUTBOUND_CONFIG, PLAYLIST_ID, POLLING_RECORD, POLL_QUEUE, SEARCH_TERM, VIDEO_ID,
+    is_blacklisted, is_blacklisted_resolved, Directory, Feature, FeatureImportance, OutboundConfig,
+    PolledOn, PollingFeaturesConfig, SavedFile, CONCURRENT_POLLS, INSTANCES_RECORD,
+    INSTANCES_STATS, METRICS, OUTBOUND_CONFIG, POLLING_HISTORY, POLLING_RECORD, POLL_QUEUE,
+    SAMPLESETS, STORAGE,
 };
 
-use super::RegionRecords;
+use super::{
+    outcome_or_latency, probe, probe_stream, remove_instances, Outcome, ProbeClient, RegionRecords,
+};
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct PollingRecord(pub HashMap<String, PolledSingleRecord>);
 
 impl SavedFile for PollingRecord {
-    const PATH: &'static str = ".local/share/vidiup/pollingrecords.json";
+    const DIR: Directory = Directory::Data;
+    const PATH: &'static str = "pollingrecords.json";
 }
 
 impl PollingRecord {
@@ -33,52 +35,51 @@ impl PollingRecord {
             return;
         }
 
-        let mut concurrent = CONCURRENT_POLLS.get().unwrap().lock().unwrap();
-        let concurrent_copy = *concurrent;
-        let max = OUTBOUND_CONFIG.get().unwrap().polling.max_concurrent;
-        *concurrent = max;
+        if !SAMPLESETS.get().unwrap().lock().unwrap().pollable() {
+            warn!("Not polling, no enabled feature has samples to poll with");
+            return;
+        }
 
-        for _ in 0..max - concurrent_copy {
+        let mut concurrent = CONCURRENT_POLLS.get().unwrap().lock().unwrap();
+        let missing = OUTBOUND_CONFIG
+            .get()
+            .unwrap()
+            .polling
+            .max_concurrent
+            .saturating_sub(*concurrent);
+        *concurrent += missing;
+
+        for _ in 0..missing {
             tokio::spawn(async {
-                loop {
+                let remaining = loop {
                     let instance = {
-                        let queue = &mut *POLL_QUEUE.get().unwrap().lock().unwrap();
+                        let mut concurrent = CONCURRENT_POLLS.get().unwrap().lock().unwrap();
+                        let max = OUTBOUND_CONFIG.get().unwrap().polling.max_concurrent;
+
+                        // stop once the queue is empty, or if max_concurrent has been lowered
+                        let next = if *concurrent > max {
+                            None
+                        } else {
+                            POLL_QUEUE.get().unwrap().lock().unwrap().pop()
+                        };
 
-                        match queue.pop() {
+                        match next {
                             Some(i) => i,
-                            None => break,
+                            None => {
+                                *concurrent -= 1;
+                                break *concurrent;
+                            }
                         }
                     };
 
-                    let record = PolledSingleRecord::poll(instance.clone()).await;
-
-                    INSTANCES_RECORD
-                        .get()
-                        .unwrap()
-                        .lock()
-                        .unwrap()
-                        .update_single(&instance, record.clone());
-                    POLLING_RECORD
-                        .get()
-                        .unwrap()
-                        .lock()
-                        .unwrap()
-                        .0
-                        .insert(instance, record);
-                }
-
-                let remaining = {
-                    let mut remaining = CONCURRENT_POLLS.get().unwrap().lock().unwrap();
-                    *remaining -= 1;
-                    *remaining
+                    Self::poll_single(instance).await;
                 };
 
                 if remaining == 0 {
-                    let records = INSTANCES_RECORD.get().unwrap().lock().unwrap().clone();
-                    let polled = POLLING_RECORD.get().unwrap().lock().unwrap().clone();
-                    *INSTANCES_STATS.get().unwrap().lock().unwrap() = records.stat();
-                    let _ = records.save().await;
-                    let _ = polled.save().await;
+                    let stats = INSTANCES_RECORD.get().unwrap().lock().unwrap().stat();
+                    *INSTANCES_STATS.get().unwrap().lock().unwrap() = stats;
+                    let _ = STORAGE.get().unwrap().save_instances().await;
+                    let _ = STORAGE.get().unwrap().save_polling().await;
                 }
             });
         }
@@ -86,6 +87,51 @@ impl PollingRecord {
 }
 
 impl PollingRecord {
+    /// Polls one instance and applies the result to the instance and polling records, returns
+    /// `None` if the instance turned out to be blacklisted and was removed instead.
+    pub async fn poll_single(instance: String) -> Option<PolledSingleRecord> {
+        if is_blacklisted_resolved(&instance).await {
+            remove_instances(std::slice::from_ref(&instance)).await;
+            warn!("Removed blacklisted instance {instance}");
+            return None;
+        }
+
+        let mut record = PolledSingleRecord::poll(instance.clone()).await;
+
+        if record.timed_out() {
+            record = PolledSingleRecord::poll(instance.clone()).await;
+        }
+        // only the attempt that counts is recorded
+        record.record_metrics();
+
+        POLLING_HISTORY
+            .get()
+            .unwrap()
+            .lock()
+            .unwrap()
+            .push(&instance, record.clone());
+
+        INSTANCES_RECORD
+            .get()
+            .unwrap()
+            .lock()
+            .unwrap()
+            .update_single(&instance, record.clone());
+        POLLING_RECORD
+            .get()
+            .unwrap()
+            .lock()
+            .unwrap()
+            .0
+            .insert(instance.clone(), record.clone());
+
+        if let Err(e) = STORAGE.get().unwrap().record_poll(&instance, &record).await {
+            warn!("Could not save poll of {instance}: {e}");
+        }
+
+        Some(record)
+    }
+
     pub fn update(&mut self, instance: String, record: PolledSingleRecord) {
         self.0.insert(instance, record);
     }
@@ -103,7 +149,7 @@ impl PollingRecord {
             .into_iter()
             .filter_map(|record| {
                 let address = record.instance.address;
-                if in_queue.contains(&address) {
+                if in_queue.contains(&address) || is_blacklisted(&address) {
                     return None;
                 }
                 let last_polled = self
@@ -123,7 +169,7 @@ impl PollingRecord {
             .into_iter()
             .filter_map(|record| {
                 let address = record.instance.address;
-                if in_queue.contains(&address) {
+                if in_queue.contains(&address) || is_blacklisted(&address) {
                     return None;
                 }
                 let last_polled = self
@@ -143,7 +189,7 @@ impl PollingRecord {
             .into_iter()
             .filter_map(|record| {
                 let address = record.instance.address;
-                if in_queue.contains(&address) {
+                if in_queue.contains(&address) || is_blacklisted(&address) {
                     return None;
                 }
                 let last_polled = self
@@ -163,7 +209,7 @@ impl PollingRecord {
             .into_iter()
             .filter_map(|record| {
                 let address = record.0.instance.address;
-                if in_queue.contains(&address) {
+                if in_queue.contains(&address) || is_blacklisted(&address) {
                     return None;
                 }
                 let last_polled = self
@@ -183,7 +229,7 @@ impl PollingRecord {
             .into_iter()
             .filter_map(|record| {
                 let address = record.address;
-                if in_queue.contains(&address) {
+                if in_queue.contains(&address) || is_blacklisted(&address) {
                     return None;
                 }
                 let last_polled = self
@@ -205,7 +251,7 @@ impl PollingRecord {
             .into_iter()
             .filter_map(|record| {
                 let address = record.instance.address;
-                if in_queue.contains(&address) {
+                if in_queue.contains(&address) || is_blacklisted(&address) {
                     return None;
                 }
                 let last_polled = self
@@ -226,7 +272,7 @@ impl PollingRecord {
             .into_iter()
             .filter_map(|record| {
                 let address = record.address;
-                if in_queue.contains(&address) {
+                if in_queue.contains(&address) || is_blacklisted(&address) {
                     return None;
                 }
                 let last_polled = self
@@ -246,7 +292,7 @@ impl PollingRecord {
             .into_iter()
             .filter_map(|record| {
                 let address = record.address;
-                if in_queue.contains(&address) {
+                if in_queue.contains(&address) || is_blacklisted(&address) {
                     return None;
                 }
                 let last_polled = self
@@ -279,46 +325,257 @@ impl PollingRecord {
 pub struct PolledSingleRecord {
     #[serde(default)]
     pub last_polled: u64,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub video: Option<u32>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub playlist: Option<u32>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub channel: Option<u32>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub search: Option<u32>,
+    #[serde(
+        default,
+        deserialize_with = "outcome_or_latency",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub video: Option<Outcome>,
+    #[serde(
+        default,
+        deserialize_with = "outcome_or_latency",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub playlist: Option<Outcome>,
+    #[serde(
+        default,
+        deserialize_with = "outcome_or_latency",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub channel: Option<Outcome>,
+    #[serde(
+        default,
+        deserialize_with = "outcome_or_latency",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub search: Option<Outcome>,
+    #[serde(
+        default,
+        deserialize_with = "outcome_or_latency",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub playback: Option<Outcome>,
+    #[serde(
+        default,
+        deserialize_with = "outcome_or_latency",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub comments: Option<Outcome>,
+    #[serde(
+        default,
+        deserialize_with = "outcome_or_latency",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub captions: Option<Outcome>,
+    #[serde(
+        default,
+        deserialize_with = "outcome_or_latency",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub trending: Option<Outcome>,
+    #[serde(
+        default,
+        deserialize_with = "outcome_or_latency",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub popular: Option<Outcome>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub polled_on: Option<PolledOn>,
+    /// Features that were enabled and had a sample when polled, kept in the history so old polls
+    /// are scored the same as they were live.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected: Option<Vec<Feature>>,
 }
 
 impl PolledSingleRecord {
+    fn outcomes(&self) -> [Option<&Outcome>; 9] {
+        [
+            self.video.as_ref(),
+            self.playlist.as_ref(),
+            self.channel.as_ref(),
+            self.search.as_ref(),
+            self.playback.as_ref(),
+            self.comments.as_ref(),
+            self.captions.as_ref(),
+            self.trending.as_ref(),
+            self.popular.as_ref(),
+        ]
+    }
+
+    fn failures(&self) -> impl Iterator<Item = &Outcome> {
+        self.outcomes()
+            .into_iter()
+            .flatten()
+            .filter(|outcome| !outcome.is_ok())
+    }
+
     pub fn score(&self) -> u32 {
-        self.video.unwrap_or(0)
-            + self.playlist.unwrap_or(0)
-            + self.search.unwrap_or(0)
-            + self.channel.unwrap_or(0)
+        self.outcomes()
+            .into_iter()
+            .flatten()
+            .filter_map(Outcome::latency)
+            .sum()
+    }
+
+    /// Enabled features that had a sample to be polled with, as recorded when polled if it was.
+    fn expected(&self, config: &OutboundConfig) -> PollingFeaturesConfig {
+        if let Some(expected) = &self.expected {
+            return expected.as_slice().into();
+        }
+
+        let mut features = config.polling.features.clone();
+
+        if let Some(polled_on) = &self.polled_on {
+            features.video &= polled_on.video.is_some();
+            features.playlist &= polled_on.playlist.is_some();
+            features.channel &= polled_on.channel.is_some();
+            features.search &= polled_on.search.is_some();
+            features.playback &= polled_on.video.is_some();
+            features.comments &= polled_on.video.is_some();
+            features.captions &= polled_on.video.is_some();
+        } else {
+            // history from before `expected` was kept, only what was polled was expected
+            features = Feature::ALL
+                .into_iter()
+                .filter(|feature| feature.enabled(&features) && feature.outcome(self).is_some())
+                .collect::<Vec<_>>()
+                .as_slice()
+                .into();
+        }
+
+        features
+    }
+
+    /// Each feature's outcome, with whether it was expected and how much it counts.
+    fn weighted(
+        &self,
+        config: &OutboundConfig,
+    ) -> [(bool, FeatureImportance, &Option<Outcome>); 9] {
+        let expected = self.expected(config);
+        let importance = &config.health.importance;
+
+        [
+            (expected.video, importance.video, &self.video),
+            (expected.playlist, importance.playlist, &self.playlist),
+            (expected.channel, importance.channel, &self.channel),
+            (expected.search, importance.search, &self.search),
+            (expected.playback, importance.playback, &self.playback),
+            (expected.comments, importance.comments, &self.comments),
+            (expected.captions, importance.captions, &self.captions),
+            (expected.trending, importance.trending, &self.trending),
+            (expected.popular, importance.popular, &self.popular),
+        ]
+    }
+
+    /// Weighted share of the expected features that worked, from 0 to 1. 1 if nothing was
+    /// expected.
+    pub fn health_score(&self) -> f64 {
+        self.health_score_in(&OUTBOUND_CONFIG.get().unwrap())
+    }
+
+    fn health_score_in(&self, config: &OutboundConfig) -> f64 {
+        let (ok, total) = self
+            .weighted(config)
+            .into_iter()
+            .filter(|(expected, _, _)| *expected)
+            .fold((0., 0.), |(ok, total), (_, importance, outcome)| {
+                (
+                    ok + if is_ok(outcome) {
+                        importance.weight
+                    } else {
+                        0.
+                    },
+                    total + importance.weight,
+                )
+            });
+
+        if total == 0. {
+            1.
+        } else {
+            ok / total
+        }
+    }
+
+    fn required_ok(&self, config: &OutboundConfig) -> bool {
+        self.weighted(config)
+            .into_iter()
+            .all(|(expected, importance, outcome)| {
+                !expected || !importance.required || is_ok(outcome)
+            })
     }
 
     pub fn well(&self) -> bool {
-        let pollings = &OUTBOUND_CONFIG.get().unwrap().polling.features;
+        self.well_in(&OUTBOUND_CONFIG.get().unwrap())
+    }
 
-        !((self.video.is_none() && pollings.video)
-            || (self.playlist.is_none() && pollings.playlist)
-            || (self.channel.is_none() && pollings.channel)
-            || (self.search.is_none() && pollings.search))
+    fn well_in(&self, config: &OutboundConfig) -> bool {
+        self.required_ok(config) && self.health_score_in(config) >= config.health.thresholds.hot
     }
 
     pub fn dead(&self) -> bool {
-        let pollings = &OUTBOUND_CONFIG.get().unwrap().polling.features;
+        self.dead_in(&OUTBOUND_CONFIG.get().unwrap())
+    }
+
+    fn dead_in(&self, config: &OutboundConfig) -> bool {
+        self.health_score_in(config) <= config.health.thresholds.dead
+    }
+
+    /// Every expected feature succeeded, so [`Self::score`] covers all of them.
+    pub fn complete(&self) -> bool {
+        self.complete_in(&OUTBOUND_CONFIG.get().unwrap())
+    }
+
+    fn complete_in(&self, config: &OutboundConfig) -> bool {
+        self.weighted(config)
+            .into_iter()
+            .all(|(expected, _, outcome)| !expected || is_ok(outcome))
+    }
+
+    /// Counts the outcome of every probe in the metrics.
+    fn record_metrics(&self) {
+        let mut metrics = METRICS.get().unwrap().lock().unwrap();
+        for (feature, outcome) in [
+            ("video", &self.video),
+            ("playlist", &self.playlist),
+            ("channel", &self.channel),
+            ("search", &self.search),
+            ("playback", &self.playback),
+            ("comments", &self.comments),
+            ("captions", &self.captions),
+            ("trending", &self.trending),
+            ("popular", &self.popular),
+        ] {
+            if let Some(outcome) = outcome {
+                metrics.probe(feature, outcome);
+            }
+        }
+    }
+
+    /// The record without the samples it was polled with, as kept in the history. Which features
+    /// were expected is kept.
+    pub fn without_samples(mut self) -> Self {
+        self.polled_on = None;
+        self
+    }
+
+    /// Every failed feature timed out, worth polling again before acting on it.
+    pub fn timed_out(&self) -> bool {
+        let mut failures = self.failures().peekable();
+        failures.peek().is_some() && failures.all(|outcome| *outcome == Outcome::Timeout)
+    }
 
-        (self.video.is_none() && pollings.video)
-            && (self.playlist.is_none() && pollings.playlist)
-            && (self.channel.is_none() && pollings.channel)
-            && (self.search.is_none() && pollings.search)
+    /// Every failed feature was refused by Invidious itself, the instance is up but YouTube is
+    /// blocking it.
+    pub fn blocked(&self) -> bool {
+        let mut failures = self.failures().peekable();
+        failures.peek().is_some()
+            && failures.all(|outcome| matches!(outcome, Outcome::Blocked { .. }))
     }
 
     pub async fn poll(instance: String) -> Self {
-        let client = ClientAsync::default().instance(format!("https://{instance}"));
-        let mainconfig = MASTER_CONFIG.get().unwrap();
+        let client = ProbeClient::new(format!("https://{instance}"));
         let outboundconfig = OUTBOUND_CONFIG.get().unwrap();
+        let polled_on = PolledOn::next();
 
         let mut set = JoinSet::new();
 
@@ -326,84 +583,356 @@ impl PolledSingleRecord {
         let playlist = Arc::new(Mutex::new(None));
         let channel = Arc::new(Mutex::new(None));
         let search = Arc::new(Mutex::new(None));
-
-        if outboundconfig.polling.features.video {
+        let playback = Arc::new(Mutex::new(None));
+        let comments = Arc::new(Mutex::new(None));
+        let captions = Arc::new(Mutex::new(None));
+        let trending = Arc::new(Mutex::new(None));
+        let popular = Arc::new(Mutex::new(None));
+
+        let features = &outboundconfig.polling.features;
+        if let Some(id) = polled_on
+            .video
+            .clone()
+            .filter(|_| features.video || features.playback)
+        {
             let video = video.clone();
+            let playback = playback.clone();
             let client = client.clone();
-            set.spawn(timeout(
-                Duration::from_millis(mainconfig.timeout as u64),
-                async move {
-                    let start = Instant::now();
-                    let id = VIDEO_ID.get().unwrap().lock().unwrap().clone();
-                    if client.video(&id, None).await.is_ok() {
-                        *video.lock().unwrap() = Some(start.elapsed().as_millis() as u32)
-                    }
-                },
-            ));
+            let features = features.clone();
+            set.spawn(async move {
+                let mut itag = None;
+                let outcome = probe(client.video(&id, None), |video| {
+                    itag = video
+                        .format_streams
+                        .first()
+                        .map(|stream| stream.itag.clone())
+                        .or_else(|| {
+                            video
+                                .adaptive_formats
+                                .first()
+                                .map(|format| format.itag.clone())
+                        });
+                    itag.is_none()
+                })
+                .await;
+
+                if features.playback {
+                    // no stream to fetch if the metadata failed, which counts against playback too
+                    let stream = match &itag {
+                        Some(itag) if outcome.is_ok() => {
+                            probe_stream(client.get_instance(), &id, itag).await
+                        }
+                        _ => outcome.clone(),
+                    };
+                    *playback.lock().unwrap() = Some(stream);
+                }
+
+                if features.video {
+                    *video.lock().unwrap() = Some(outcome)
+                }
+            });
         }
 
-        if outboundconfig.polling.features.playlist {
+        if let Some(id) = polled_on
+            .playlist
+            .clone()
+            .filter(|_| outboundconfig.polling.features.playlist)
+        {
             let playlist = playlist.clone();
             let client = client.clone();
-            set.spawn(timeout(
-                Duration::from_millis(mainconfig.timeout as u64),
-                async move {
-                    let start = Instant::now();
-                    let id = PLAYLIST_ID.get().unwrap().lock().unwrap().clone();
-                    if client.playlist(&id, None).await.is_ok() {
-                        *playlist.lock().unwrap() = Some(start.elapsed().as_millis() as u32)
-                    }
-                },
-            ));
+            set.spawn(async move {
+                let outcome = probe(client.playlist(&id, None), |playlist| {
+                    playlist.videos.is_empty()
+                })
+                .await;
+                *playlist.lock().unwrap() = Some(outcome)
+            });
         }
 
-        if outboundconfig.polling.features.channel {
+        if let Some(id) = polled_on
+            .channel
+            .clone()
+            .filter(|_| outboundconfig.polling.features.channel)
+        {
             let channel = channel.clone();
             let client = client.clone();
-            set.spawn(timeout(
-                Duration::from_millis(mainconfig.timeout as u64),
-                async move {
-                    let start = Instant::now();
-                    let id = CHANNEL_ID.get().unwrap().lock().unwrap().clone();
-                    if client.channel(&id, None).await.is_ok() {
-                        *channel.lock().unwrap() = Some(start.elapsed().as_millis() as u32)
-                    }
-                },
-            ));
+            set.spawn(async move {
+                let outcome =
+                    probe(client.channel(&id, None), |channel| channel.name.is_empty()).await;
+                *channel.lock().unwrap() = Some(outcome)
+            });
         }
 
-        if outboundconfig.polling.features.search {
+        if let Some(id) = polled_on
+            .search
+            .clone()
+            .filter(|_| outboundconfig.polling.features.search)
+        {
             let search = search.clone();
             let client = client.clone();
-            set.spawn(timeout(
-                Duration::from_millis(mainconfig.timeout as u64),
-                async move {
-                    let start = Instant::now();
-                    let id = SEARCH_TERM.get().unwrap().lock().unwrap().clone();
-                    if client
-                        .search(Some(format!("q={id}").as_str()))
-                        .await
-                        .is_ok()
-                    {
-                        *search.lock().unwrap() = Some(start.elapsed().as_millis() as u32)
-                    }
-                },
-            ));
+            set.spawn(async move {
+                let outcome = probe(client.search(Some(format!("q={id}").as_str())), |search| {
+                    search.items.is_empty()
+                })
+                .await;
+                *search.lock().unwrap() = Some(outcome)
+            });
         }
 
-        while set.join_next().await.is_some() {}
+        if let Some(id) = polled_on.video.clone().filter(|_| features.comments) {
+            let comments = comments.clone();
+            let client = client.clone();
+            set.spawn(async move {
+                let outcome = probe(client.comments(&id, None), |comments| {
+                    comments.comments.is_empty()
+                })
+                .await;
+                *comments.lock().unwrap() = Some(outcome)
+            });
+        }
 
-        let video = *video.lock().unwrap();
-        let playlist = *playlist.lock().unwrap();
-        let channel = *channel.lock().unwrap();
-        let search = *search.lock().unwrap();
+        if let Some(id) = polled_on.video.clone().filter(|_| features.captions) {
+            let captions = captions.clone();
+            let client = client.clone();
+            set.spawn(async move {
+                let outcome = probe(client.captions(&id, None), |captions| {
+                    captions.captions.is_empty()
+                })
+                .await;
+                *captions.lock().unwrap() = Some(outcome)
+            });
+        }
+
+        if features.trending {
+            let trending = trending.clone();
+            let client = client.clone();
+            set.spawn(async move {
+                let outcome =
+                    probe(client.trending(None), |trending| trending.videos.is_empty()).await;
+                *trending.lock().unwrap() = Some(outcome)
+            });
+        }
 
-        Self {
+        if features.popular {
+            let popular = popular.clone();
+            let client = client.clone();
+            set.spawn(async move {
+                let outcome = probe(client.popular(None), |popular| popular.items.is_empty()).await;
+                *popular.lock().unwrap() = Some(outcome)
+            });
+        }
+
+        while set.join_next().await.is_some() {}
+
+        let video = video.lock().unwrap().clone();
+        let playlist = playlist.lock().unwrap().clone();
+        let channel = channel.lock().unwrap().clone();
+        let search = search.lock().unwrap().clone();
+        let playback = playback.lock().unwrap().clone();
+        let comments = comments.lock().unwrap().clone();
+        let captions = captions.lock().unwrap().clone();
+        let trending = trending.lock().unwrap().clone();
+        let popular = popular.lock().unwrap().clone();
+
+        let mut record = Self {
             last_polled: Utc::now().timestamp() as u64,
             video,
             playlist,
             channel,
             search,
+            playback,
+            comments,
+            captions,
+            trending,
+            popular,
+            polled_on: Some(polled_on),
+            expected: None,
+        };
+
+        let expected = record.expected(&outboundconfig);
+        record.expected = Some(
+            Feature::ALL
+                .into_iter()
+                .filter(|feature| feature.enabled(&expected))
+                .collect(),
+        );
+
+        record
+    }
+}
+
+fn is_ok(outcome: &Option<Outcome>) -> bool {
+    outcome.as_ref().is_some_and(Outcome::is_ok)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A poll of video, playlist, channel and search where the `failed` ones timed out.
+    fn polled(failed: &[&str]) -> PolledSingleRecord {
+        let outcome = |feature| {
+            Some(if failed.contains(&feature) {
+                Outcome::Timeout
+            } else {
+                Outcome::Ok { latency: 100 }
+            })
+        };
+
+        PolledSingleRecord {
+            video: outcome("video"),
+            playlist: outcome("playlist"),
+            channel: outcome("channel"),
+            search: outcome("search"),
+            ..Default::default()
         }
     }
+
+    /// Defaults: video 5 (required), playlist 2, channel 2, search 1, hot at 0.9, dead at 0.
+    fn config(hot: f64, dead: f64) -> OutboundConfig {
+        let mut config = OutboundConfig::default();
+        config.health.thresholds.hot = hot;
+        config.health.thresholds.dead = dead;
+        config
+    }
+
+    struct Case {
+        failed: &'static [&'static str],
+        hot: f64,
+        dead: f64,
+        score: f64,
+        required_ok: bool,
+        well: bool,
+        is_dead: bool,
+    }
+
+    #[test]
+    fn classification() {
+        #[rustfmt::skip]
+        let cases = [
+            Case { failed: &[], hot: 0.9, dead: 0., score: 1., required_ok: true, well: true, is_dead: false },
+            // required failure, never well
+            Case { failed: &["video"], hot: 0.5, dead: 0., score: 0.5, required_ok: false, well: false, is_dead: false },
+            Case { failed: &["video"], hot: 0.1, dead: 0., score: 0.5, required_ok: false, well: false, is_dead: false },
+            // optional failures, on and below the hot boundary
+            Case { failed: &["search"], hot: 0.9, dead: 0., score: 0.9, required_ok: true, well: true, is_dead: false },
+            Case { failed: &["playlist"], hot: 0.9, dead: 0., score: 0.8, required_ok: true, well: false, is_dead: false },
+            Case { failed: &["playlist"], hot: 0.8, dead: 0., score: 0.8, required_ok: true, well: true, is_dead: false },
+            // on and above the dead boundary
+            Case { failed: &["video", "playlist", "channel", "search"], hot: 0.9, dead: 0., score: 0., required_ok: false, well: false, is_dead: true },
+            Case { failed: &["video"], hot: 0.9, dead: 0.5, score: 0.5, required_ok: false, well: false, is_dead: true },
+            Case { failed: &["video"], hot: 0.9, dead: 0.49, score: 0.5, required_ok: false, well: false, is_dead: false },
+        ];
+
+        for case in cases {
+            let config = config(case.hot, case.dead);
+            let record = polled(case.failed);
+            let failed = case.failed;
+
+            assert_eq!(record.health_score_in(&config), case.score, "{failed:?}");
+            assert_eq!(record.required_ok(&config), case.required_ok, "{failed:?}");
+            assert_eq!(
+                record.well_in(&config),
+                case.well,
+                "{failed:?} hot {}",
+                case.hot
+            );
+            assert_eq!(
+                record.dead_in(&config),
+                case.is_dead,
+                "{failed:?} dead {}",
+                case.dead
+            );
+        }
+    }
+
+    #[test]
+    fn zero_total_weight() {
+        let mut config = config(0.9, 0.);
+        for importance in [
+            &mut config.health.importance.video,
+            &mut config.health.importance.playlist,
+            &mut config.health.importance.channel,
+            &mut config.health.importance.search,
+        ] {
+            importance.weight = 0.;
+            importance.required = false;
+        }
+
+        let record = polled(&["video", "playlist", "channel", "search"]);
+        assert_eq!(record.health_score_in(&config), 1.);
+        assert!(record.well_in(&config));
+        assert!(!record.dead_in(&config));
+    }
+
+    #[test]
+    fn nothing_expected() {
+        let mut config = config(0.9, 0.);
+        config.polling.features.video = false;
+        config.polling.features.playlist = false;
+        config.polling.features.channel = false;
+        config.polling.features.search = false;
+
+        let record = polled(&["video", "playlist", "channel", "search"]);
+        assert_eq!(record.health_score_in(&config), 1.);
+        assert!(record.required_ok(&config));
+    }
+
+    #[test]
+    fn unsampled_features_not_expected() {
+        let config = config(0.9, 0.);
+        let mut record = polled(&["search"]);
+        record.polled_on = Some(PolledOn {
+            video: Some("id".to_string()),
+            playlist: Some("id".to_string()),
+            channel: Some("id".to_string()),
+            search: None,
+        });
+
+        assert_eq!(record.health_score_in(&config), 1.);
+        assert!(record.well_in(&config));
+    }
+
+    #[test]
+    fn recorded_expected_outlives_config() {
+        let mut config = config(0.9, 0.);
+        let mut record = polled(&[]);
+        record.expected = Some(vec![
+            Feature::Video,
+            Feature::Playlist,
+            Feature::Channel,
+            Feature::Search,
+        ]);
+
+        // enabled after the poll, so never polled
+        config.polling.features.playback = true;
+        assert_eq!(record.health_score_in(&config), 1.);
+        assert!(record.well_in(&config));
+
+        // the history keeps it
+        let record = record.without_samples();
+        assert!(record.expected.is_some());
+        assert!(record.well_in(&config));
+    }
+
+    #[test]
+    fn legacy_history_expects_what_was_polled() {
+        let mut config = config(0.9, 0.);
+        config.polling.features.playback = true;
+
+        let record = polled(&["search"]).without_samples();
+        assert_eq!(record.health_score_in(&config), 0.9);
+        assert!(record.well_in(&config));
+    }
+
+    #[test]
+    fn complete_only_without_failures() {
+        let config = config(0.9, 0.);
+
+        assert!(polled(&[]).complete_in(&config));
+        // well, but the score is missing search
+        assert!(polled(&["search"]).well_in(&config));
+        assert!(!polled(&["search"]).complete_in(&config));
+        assert!(!polled(&["video"]).complete_in(&config));
+    }
 }
@@ -0,0 +1,226 @@
+use std::{error::Error, fmt::Display, future::Future, time::Duration};
+
+use futures_lite::AsyncReadExt;
+use invidious::{
+    isahc::{
+        self,
+        config::{Configurable, RedirectPolicy},
+        AsyncReadResponseExt,
+    },
+    ClientAsyncTrait, InvidiousError,
+};
+use serde::{Deserialize, Deserializer, Serialize};
+use tokio::time::{timeout, Instant};
+
+use crate::MASTER_CONFIG;
+
+/// Result of probing a single feature on an instance.
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase", tag = "outcome")]
+pub enum Outcome {
+    Ok {
+        latency: u32,
+    },
+    /// No response within `timeout` from `master.json`.
+    Timeout,
+    /// Connection, DNS or TLS failure, the instance is most likely down.
+    Connect,
+    /// The instance answered with a non-2xx status.
+    Http {
+        status: u16,
+    },
+    /// The response was not the JSON we expected.
+    BadJson,
+    /// Invidious returned an error message, usually YouTube blocking the instance.
+    Blocked {
+        message: String,
+    },
+    /// The response parsed, but had nothing in it.
+    Empty,
+}
+
+impl Outcome {
+    pub fn latency(&self) -> Option<u32> {
+        match self {
+            Self::Ok { latency } => Some(*latency),
+            _ => None,
+        }
+    }
+
+    /// Name of the variant, as it is serialised.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Ok { .. } => "ok",
+            Self::Timeout => "timeout",
+            Self::Connect => "connect",
+            Self::Http { .. } => "http",
+            Self::BadJson => "badJson",
+            Self::Blocked { .. } => "blocked",
+            Self::Empty => "empty",
+        }
+    }
+
+    /// Adds a region's offset to the latency, if there is one.
+    pub fn offset(&mut self, offset: i32) {
+        if let Self::Ok { latency } = self {
+            *latency = latency.saturating_add_signed(offset);
+        }
+    }
+
+    pub fn is_ok(&self) -> bool {
+        matches!(self, Self::Ok { .. })
+    }
+
+    fn from_error(error: InvidiousError) -> Self {
+        match error {
+            InvidiousError::Fetch { error } => match error.downcast_ref::<StatusError>() {
+                Some(StatusError(status)) => Self::Http { status: *status },
+                None => Self::Connect,
+            },
+            InvidiousError::ApiError { message } | InvidiousError::Message { message } => {
+                Self::Blocked { message }
+            }
+            InvidiousError::SerdeError { .. } => Self::BadJson,
+        }
+    }
+}
+
+/// Polling records written before outcomes were recorded store a bare latency.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum OutcomeRepr {
+    Latency(u32),
+    Outcome(Outcome),
+}
+
+/// Deserialises an optional [`Outcome`], reading a bare latency as [`Outcome::Ok`].
+pub fn outcome_or_latency<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Outcome>, D::Error> {
+    Ok(
+        Option::<OutcomeRepr>::deserialize(deserializer)?.map(|repr| match repr {
+            OutcomeRepr::Latency(latency) => Outcome::Ok { latency },
+            OutcomeRepr::Outcome(outcome) => outcome,
+        }),
+    )
+}
+
+/// Times `request`, classifying the result into an [`Outcome`].
+pub async fn probe<T>(
+    request: impl Future<Output = Result<T, InvidiousError>>,
+    empty: impl FnOnce(&T) -> bool,
+) -> Outcome {
+    let duration = Duration::from_millis(MASTER_CONFIG.get().unwrap().timeout as u64);
+    let start = Instant::now();
+
+    match timeout(duration, request).await {
+        Err(_) => Outcome::Timeout,
+        Ok(Ok(res)) if empty(&res) => Outcome::Empty,
+        Ok(Ok(_)) => Outcome::Ok {
+            latency: start.elapsed().as_millis() as u32,
+        },
+        Ok(Err(e)) => Outcome::from_error(e),
+    }
+}
+
+/// Bytes fetched from the start of a stream by [`probe_stream`].
+const STREAM_BYTES: u64 = 4096;
+
+/// Fetches the start of stream `itag` of video `id` through the instance's own proxy, as a
+/// player with "proxy videos" on would. Metadata can load fine while the stream itself is
+/// blocked.
+pub async fn probe_stream(instance: &str, id: &str, itag: &str) -> Outcome {
+    let duration = Duration::from_millis(MASTER_CONFIG.get().unwrap().timeout as u64);
+    let start = Instant::now();
+
+    let request = async {
+        let request = isahc::Request::get(format!(
+            "{instance}/latest_version?id={id}&itag={itag}&local=true"
+        ))
+        .header("Range", format!("bytes=0-{}", STREAM_BYTES - 1))
+        .redirect_policy(RedirectPolicy::Limit(5))
+        .body(())
+        .map_err(|_| Outcome::Connect)?;
+        let mut res = isahc::send_async(request)
+            .await
+            .map_err(|_| Outcome::Connect)?;
+
+        let status = res.status();
+        if !status.is_success() {
+            return Err(Outcome::Http {
+                status: status.as_u16(),
+            });
+        }
+
+        // servers ignoring the range would otherwise send the whole video
+        let mut bytes = Vec::new();
+        res.body_mut()
+            .take(STREAM_BYTES)
+            .read_to_end(&mut bytes)
+            .await
+            .map_err(|_| Outcome::Connect)?;
+
+        Ok(bytes.len())
+    };
+
+    match timeout(duration, request).await {
+        Err(_) => Outcome::Timeout,
+        Ok(Ok(0)) => Outcome::Empty,
+        Ok(Ok(_)) => Outcome::Ok {
+            latency: start.elapsed().as_millis() as u32,
+        },
+        Ok(Err(outcome)) => outcome,
+    }
+}
+
+#[derive(Debug)]
+struct StatusError(u16);
+
+impl Display for StatusError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "HTTP status {}", self.0)
+    }
+}
+
+impl Error for StatusError {}
+
+/// Invidious client that fails on non-2xx responses instead of trying to parse the body, so the
+/// status code ends up in the [`Outcome`].
+#[derive(Clone)]
+pub struct ProbeClient {
+    instance: String,
+}
+
+#[async_trait::async_trait]
+impl ClientAsyncTrait for ProbeClient {
+    fn new(instance: String) -> Self {
+        Self { instance }
+    }
+
+    fn set_instance(&mut self, instance: String) {
+        self.instance = instance;
+    }
+
+    fn get_instance(&self) -> &str {
+        &self.instance
+    }
+
+    async fn fetch(&self, url: &str) -> Result<String, Box<dyn Error>> {
+        let mut res =
+            isahc::get_async(format!("{}/{}", self.instance, url.trim_start_matches('/'))).await?;
+
+        let status = res.status();
+        let text = res.text().await?;
+
+        // Invidious reports YouTube errors as a JSON `error` with a 5xx status, pass those
+        // through so they become `Outcome::Blocked`
+        if !status.is_success()
+            && serde_json::from_str::<serde_json::Value>(&text)
+                .map_or(true, |value| !value["error"].is_string())
+        {
+            return Err(Box::new(StatusError(status.as_u16())));
+        }
+
+        Ok(text)
+    }
+}
@@ -1,10 +1,11 @@
 use std::sync::{Arc, Mutex};
 
+use log::warn;
 use serde::{Deserialize, Serialize};
 
 use crate::*;
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct Samples {
     pub video: SampleSet,
@@ -14,11 +15,26 @@ pub struct Samples {
 }
 
 impl SavedFile for Samples {
-    const PATH: &'static str = ".local/share/vidiup/sampleset.json";
+    const DIR: Directory = Directory::Data;
+    const PATH: &'static str = "sampleset.json";
 }
 
 impl Samples {
     pub fn init(&self) {
+        for (name, set) in [
+            ("video", &self.video),
+            ("playlist", &self.playlist),
+            ("channel", &self.channel),
+            ("search", &self.search),
+        ] {
+            if set.0.is_empty() {
+                warn!(
+                    "No {name} samples in {}, {name} will not be polled until some are added",
+                    Self::path().display()
+                );
+            }
+        }
+
         VIDEO_ID
             .set(Arc::new(Mutex::new(self.video.gen())))
             .unwrap();
@@ -33,6 +49,19 @@ impl Samples {
             .unwrap();
     }
 
+    /// Whether any enabled feature has samples to be polled with.
+    pub fn pollable(&self) -> bool {
+        let features = &OUTBOUND_CONFIG.get().unwrap().polling.features;
+
+        ((features.video || features.playback || features.comments || features.captions)
+            && !self.video.0.is_empty())
+            || features.trending
+            || features.popular
+            || (features.playlist && !self.playlist.0.is_empty())
+            || (features.channel && !self.channel.0.is_empty())
+            || (features.search && !self.search.0.is_empty())
+    }
+
     pub fn gen(&self) {
         let video = self.video.gen();
         let playlist = self.playlist.gen();
@@ -46,15 +75,61 @@ impl Samples {
     }
 }
 
+/// Sample values an instance was polled with, `None` where there were no samples.
 #[derive(Serialize, Deserialize, Clone)]
+pub struct PolledOn {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub video: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub playlist: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub channel: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub search: Option<String>,
+}
+
+impl PolledOn {
+    /// The current global samples.
+    pub fn get() -> Self {
+        Self {
+            video: VIDEO_ID.get().unwrap().lock().unwrap().clone(),
+            playlist: PLAYLIST_ID.get().unwrap().lock().unwrap().clone(),
+            channel: CHANNEL_ID.get().unwrap().lock().unwrap().clone(),
+            search: SEARCH_TERM.get().unwrap().lock().unwrap().clone(),
+        }
+    }
+
+    /// Samples to use for the next poll, freshly picked if rotating on every poll.
+    pub fn next() -> Self {
+        match OUTBOUND_CONFIG.get().unwrap().sample_rotation {
+            SampleRotation::PerPoll => {
+                let samples = SAMPLESETS.get().unwrap().lock().unwrap();
+                Self {
+                    video: samples.video.gen(),
+                    playlist: samples.playlist.gen(),
+                    channel: samples.channel.gen(),
+                    search: samples.search.gen(),
+                }
+            }
+            _ => Self::get(),
+        }
+    }
+}
+
+/// Samples of one kind, picked from at random.
+///
+/// Video samples are also used for the comments and captions probes, which report
+/// [`Outcome::Empty`] for a video without any, so every video sample needs both.
+#[derive(Serialize, Deserialize, Clone, Default)]
 pub struct SampleSet(Vec<String>);
 
 impl SampleSet {
-    pub fn gen(&self) -> String {
+    /// Picks a random sample, if there are any.
+    pub fn gen(&self) -> Option<String> {
         if self.0.is_empty() {
-            panic!("sample set cannot be empty");
+            return None;
         }
 
-        self.0[fastrand::usize(..self.0.len())].to_string()
+        Some(self.0[fastrand::usize(..self.0.len())].to_string())
     }
 }
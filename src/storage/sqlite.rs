@@ -0,0 +1,576 @@
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use async_trait::async_trait;
+use log::info;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+
+use crate::*;
+
+const SCHEMA: &str = "
+CREATE TABLE instances (
+    address TEXT PRIMARY KEY,
+    region TEXT NOT NULL,
+    bucket TEXT NOT NULL,
+    seq INTEGER NOT NULL,
+    record TEXT NOT NULL
+);
+CREATE TABLE polling (
+    address TEXT PRIMARY KEY,
+    record TEXT NOT NULL
+);
+CREATE TABLE history (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    address TEXT NOT NULL,
+    record TEXT NOT NULL
+);
+CREATE INDEX history_address ON history (address, id);
+";
+
+/// Where an instance sits in [`InstancesRecords`], and its entry.
+///
+/// `seq` only orders the entries of a bucket. An instance keeps it while it stays in the same
+/// bucket and gets the next one when it moves, so a move only rewrites the row that moved.
+#[derive(Clone)]
+struct InstanceRow {
+    region: String,
+    bucket: &'static str,
+    seq: i64,
+    entry: Entry,
+}
+
+/// A bucket entry, serialised as it is in `instances.json`.
+#[derive(Clone, PartialEq, Serialize)]
+#[serde(untagged)]
+enum Entry {
+    Hot(HotRecord),
+    Dead(DeadRecord, f64),
+    StashedDead(DeadRecord),
+    Instance(InstanceRecord),
+}
+
+impl Entry {
+    fn address(&self) -> &str {
+        match self {
+            Self::Hot(record) => &record.instance.address,
+            Self::Dead(record, _) | Self::StashedDead(record) => &record.instance.address,
+            Self::Instance(record) => &record.address,
+        }
+    }
+
+    fn parse(bucket: &str, json: &str) -> serde_json::Result<Self> {
+        Ok(match bucket {
+            "hot" | "recovered" | "recovering" => Self::Hot(serde_json::from_str(json)?),
+            "dead" => {
+                let (record, weight) = serde_json::from_str(json)?;
+                Self::Dead(record, weight)
+            }
+            "stashedDead" => Self::StashedDead(serde_json::from_str(json)?),
+            _ => Self::Instance(serde_json::from_str(json)?),
+        })
+    }
+
+    /// Adds the entry to `bucket` of `records`, ignoring unknown buckets.
+    fn push_to(self, records: &mut RegionRecords, bucket: &str) {
+        match (bucket, self) {
+            ("hot", Self::Hot(record)) => records.hot.push(record),
+            ("recovered", Self::Hot(record)) => records.recovered.push(record),
+            ("recovering", Self::Hot(record)) => records.recovering.push(record),
+            ("dead", Self::Dead(record, weight)) => records.dead.push((record, weight)),
+            ("stashedDead", Self::StashedDead(record)) => records.stashed_dead.push(record),
+            ("stashedRecovering", Self::Instance(record)) => {
+                records.stashed_recovering.push(record)
+            }
+            ("stashed", Self::Instance(record)) => records.stashed.push(record),
+            ("pending", Self::Instance(record)) => records.pending.push(record),
+            _ => {}
+        }
+    }
+}
+
+/// Loads a JSON file to import, empty if there is none.
+async fn import<T: SavedFile + Default + Send>() -> Result<T, Box<dyn Error>> {
+    T::load_existing()
+        .await
+        .map(Option::unwrap_or_default)
+        .map_err(|e| format!("cannot import {}: {e}", T::path().display()).into())
+}
+
+/// What the database currently holds, so saves only write what changed.
+#[derive(Default)]
+struct Snapshot {
+    instances: HashMap<String, InstanceRow>,
+    polled: HashMap<String, String>,
+    history: HashSet<String>,
+}
+
+/// Keeps everything in one SQLite database, saves only write the rows that changed since the
+/// last save, and every poll is written as it happens.
+pub struct SqliteStorage {
+    connection: Arc<Mutex<Connection>>,
+    snapshot: tokio::sync::Mutex<Snapshot>,
+}
+
+impl SqliteStorage {
+    /// Opens the database at `path`, creating it and importing the JSON files if it is new.
+    pub async fn open(path: PathBuf) -> Result<Self, Box<dyn Error>> {
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+
+        let connection = Connection::open(&path)?;
+        connection.pragma_update(None, "journal_mode", "WAL")?;
+        let version: u32 = connection.pragma_query_value(None, "user_version", |row| row.get(0))?;
+
+        let storage = Self {
+            connection: Arc::new(Mutex::new(connection)),
+            snapshot: Default::default(),
+        };
+
+        if version == 0 {
+            storage.migrate(&path).await?;
+        }
+
+        Ok(storage)
+    }
+
+    /// One-shot import of the JSON files, if there are any.
+    ///
+    /// A file that exists but cannot be loaded fails the import and leaves the database
+    /// unversioned, so it is tried again on the next start instead of losing the file's contents.
+    async fn migrate(&self, path: &std::path::Path) -> Result<(), Box<dyn Error>> {
+        let instances = import::<InstancesRecords>().await?;
+        let polled = import::<PollingRecord>().await?;
+        let history = import::<PollingHistory>().await?;
+
+        let instance_rows = instance_rows(&instances, &HashMap::new())
+            .into_iter()
+            .map(|(address, row)| {
+                Ok((
+                    address,
+                    row.region,
+                    row.bucket,
+                    row.seq,
+                    serde_json::to_string(&row.entry)?,
+                ))
+            })
+            .collect::<Result<Vec<_>, serde_json::Error>>()?;
+        let polled_rows = polled_rows(&polled);
+        let history_rows = history
+            .0
+            .iter()
+            .map(|(address, history)| {
+                let records = history
+                    .0
+                    .iter()
+                    .map(serde_json::to_string)
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok((address.clone(), records))
+            })
+            .collect::<Result<Vec<_>, serde_json::Error>>()?;
+
+        let counts = (instance_rows.len(), polled_rows.len(), history_rows.len());
+
+        self.blocking(move |connection| {
+            let transaction = connection.transaction()?;
+            transaction.execute_batch(SCHEMA)?;
+            write_instances(&transaction, &instance_rows, &[])?;
+            write_polled(&transaction, &polled_rows, &[])?;
+
+            for (address, records) in history_rows {
+                for record in records {
+                    transaction.execute(
+                        "INSERT INTO history (address, record) VALUES (?1, ?2)",
+                        params![address, record],
+                    )?;
+                }
+            }
+
+            transaction.pragma_update(None, "user_version", 1)?;
+            transaction.commit()
+        })
+        .await?;
+
+        info!(
+            "Created {}, imported {} instances, {} polling records and the history of {} \
+             instances from JSON",
+            path.display(),
+            counts.0,
+            counts.1,
+            counts.2
+        );
+
+        Ok(())
+    }
+
+    async fn blocking<T: Send + 'static>(
+        &self,
+        f: impl FnOnce(&mut Connection) -> rusqlite::Result<T> + Send + 'static,
+    ) -> Result<T, Box<dyn Error>> {
+        let connection = self.connection.clone();
+        Ok(tokio::task::spawn_blocking(move || f(&mut connection.lock().unwrap())).await??)
+    }
+}
+
+#[async_trait]
+impl Storage for SqliteStorage {
+    async fn load(
+        &self,
+    ) -> Result<(InstancesRecords, PollingRecord, PollingHistory), Box<dyn Error>> {
+        let (instances, polled, history) = self
+            .blocking(|connection| {
+                let instances = connection
+                    .prepare(
+                        "SELECT address, region, bucket, seq, record FROM instances
+                         ORDER BY region, bucket, seq",
+                    )?
+                    .query_map([], |row| {
+                        Ok((
+                            row.get::<_, String>(1)?,
+                            row.get::<_, String>(2)?,
+                            row.get::<_, i64>(3)?,
+                            row.get::<_, String>(4)?,
+                        ))
+                    })?
+                    .collect::<Result<Vec<_>, _>>()?;
+                let polled = connection
+                    .prepare("SELECT address, record FROM polling")?
+                    .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+                    .collect::<Result<Vec<(String, String)>, _>>()?;
+                let history = connection
+                    .prepare("SELECT address, record FROM history ORDER BY id")?
+                    .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+                    .collect::<Result<Vec<(String, String)>, _>>()?;
+
+                Ok((instances, polled, history))
+            })
+            .await?;
+
+        let mut instances_record = InstancesRecords::default();
+        let mut rows = HashMap::new();
+        for (region, bucket, seq, record) in instances {
+            let Some(bucket) = BUCKETS.iter().find(|name| **name == bucket) else {
+                continue;
+            };
+            let entry = Entry::parse(bucket, &record)?;
+
+            rows.insert(
+                entry.address().to_string(),
+                InstanceRow {
+                    region: region.clone(),
+                    bucket,
+                    seq,
+                    entry: entry.clone(),
+                },
+            );
+            entry.push_to(instances_record.0.entry(region).or_default(), bucket);
+        }
+
+        let mut polling_record = PollingRecord::default();
+        for (address, record) in polled.iter() {
+            polling_record
+                .0
+                .insert(address.clone(), serde_json::from_str(record)?);
+        }
+
+        let size = OUTBOUND_CONFIG.get().unwrap().history.size;
+        let mut polling_history = PollingHistory::default();
+        for (address, record) in history {
+            polling_history
+                .0
+                .entry(address)
+                .or_default()
+                .push(serde_json::from_str(&record)?, size);
+        }
+
+        let mut snapshot = self.snapshot.lock().await;
+        snapshot.instances = rows;
+        snapshot.polled = polled.into_iter().collect();
+        snapshot.history = polling_history.0.keys().cloned().collect();
+
+        Ok((instances_record, polling_record, polling_history))
+    }
+
+    async fn save_instances(&self) -> Result<(), Box<dyn Error>> {
+        let mut snapshot = self.snapshot.lock().await;
+        let rows = instance_rows(
+            &INSTANCES_RECORD.get().unwrap().lock().unwrap(),
+            &snapshot.instances,
+        );
+
+        let changed = rows
+            .iter()
+            .filter(|(address, row)| {
+                snapshot.instances.get(*address).is_none_or(|old| {
+                    old.seq != row.seq || old.region != row.region || old.entry != row.entry
+                })
+            })
+            .map(|(address, row)| {
+                Ok((
+                    address.clone(),
+                    row.region.clone(),
+                    row.bucket,
+                    row.seq,
+                    serde_json::to_string(&row.entry)?,
+                ))
+            })
+            .collect::<Result<Vec<_>, serde_json::Error>>()?;
+        let removed = snapshot
+            .instances
+            .keys()
+            .filter(|address| !rows.contains_key(*address))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        if changed.is_empty() && removed.is_empty() {
+            return Ok(());
+        }
+
+        self.blocking(move |connection| {
+            let transaction = connection.transaction()?;
+            write_instances(&transaction, &changed, &removed)?;
+            transaction.commit()
+        })
+        .await?;
+
+        snapshot.instances = rows;
+        Ok(())
+    }
+
+    async fn save_polling(&self) -> Result<(), Box<dyn Error>> {
+        let mut snapshot = self.snapshot.lock().await;
+        let rows = polled_rows(&POLLING_RECORD.get().unwrap().lock().unwrap())
+            .into_iter()
+            .collect::<HashMap<_, _>>();
+        let histories = POLLING_HISTORY
+            .get()
+            .unwrap()
+            .lock()
+            .unwrap()
+            .0
+            .keys()
+            .cloned()
+            .collect::<HashSet<_>>();
+
+        let changed = rows
+            .iter()
+            .filter(|(address, record)| snapshot.polled.get(*address) != Some(record))
+            .map(|(address, record)| (address.clone(), record.clone()))
+            .collect::<Vec<_>>();
+        let removed = snapshot
+            .polled
+            .keys()
+            .filter(|address| !rows.contains_key(*address))
+            .cloned()
+            .collect::<Vec<_>>();
+        let removed_history = snapshot
+            .history
+            .difference(&histories)
+            .cloned()
+            .collect::<Vec<_>>();
+
+        if changed.is_empty() && removed.is_empty() && removed_history.is_empty() {
+            return Ok(());
+        }
+
+        self.blocking(move |connection| {
+            let transaction = connection.transaction()?;
+            write_polled(&transaction, &changed, &removed)?;
+            for address in removed_history {
+                transaction.execute("DELETE FROM history WHERE address = ?1", [address])?;
+            }
+            transaction.commit()
+        })
+        .await?;
+
+        snapshot.polled = rows;
+        snapshot.history = histories;
+        Ok(())
+    }
+
+    async fn record_poll(
+        &self,
+        instance: &str,
+        record: &PolledSingleRecord,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut snapshot = self.snapshot.lock().await;
+        let address = instance.to_string();
+        let json = serde_json::to_string(record)?;
+        let history_json = serde_json::to_string(&record.clone().without_samples())?;
+        let size = OUTBOUND_CONFIG.get().unwrap().history.size;
+
+        {
+            let address = address.clone();
+            let json = json.clone();
+            self.blocking(move |connection| {
+                let transaction = connection.transaction()?;
+                write_polled(&transaction, &[(address.clone(), json)], &[])?;
+                transaction.execute(
+                    "INSERT INTO history (address, record) VALUES (?1, ?2)",
+                    params![address, history_json],
+                )?;
+                trim_history(&transaction, &address, size)?;
+                transaction.commit()
+            })
+            .await?;
+        }
+
+        snapshot.polled.insert(address.clone(), json);
+        snapshot.history.insert(address);
+        Ok(())
+    }
+}
+
+const BUCKETS: [&str; 8] = [
+    "hot",
+    "recovered",
+    "recovering",
+    "dead",
+    "stashedRecovering",
+    "stashedDead",
+    "stashed",
+    "pending",
+];
+
+/// Rows for every instance, keeping the `seq` of those still in the same bucket as in
+/// `previous`, and numbering the rest after the highest one there.
+fn instance_rows(
+    records: &InstancesRecords,
+    previous: &HashMap<String, InstanceRow>,
+) -> HashMap<String, InstanceRow> {
+    let mut next = previous.values().map(|row| row.seq + 1).max().unwrap_or(0);
+    let mut rows = HashMap::new();
+
+    for (region, records) in records.0.iter() {
+        let hot = |records: &[HotRecord]| records.iter().cloned().map(Entry::Hot).collect();
+        let plain =
+            |records: &[InstanceRecord]| records.iter().cloned().map(Entry::Instance).collect();
+        let buckets: [Vec<Entry>; 8] = [
+            hot(&records.hot),
+            hot(&records.recovered),
+            hot(&records.recovering),
+            records
+                .dead
+                .iter()
+                .cloned()
+                .map(|(record, weight)| Entry::Dead(record, weight))
+                .collect(),
+            plain(&records.stashed_recovering),
+            records
+                .stashed_dead
+                .iter()
+                .cloned()
+                .map(Entry::StashedDead)
+                .collect(),
+            plain(&records.stashed),
+            plain(&records.pending),
+        ];
+
+        for (bucket, entries) in BUCKETS.into_iter().zip(buckets) {
+            for entry in entries {
+                let address = entry.address().to_string();
+                let seq = match previous.get(&address) {
+                    Some(row) if row.region == *region && row.bucket == bucket => row.seq,
+                    _ => {
+                        next += 1;
+                        next - 1
+                    }
+                };
+
+                rows.insert(
+                    address,
+                    InstanceRow {
+                        region: region.clone(),
+                        bucket,
+                        seq,
+                        entry,
+                    },
+                );
+            }
+        }
+    }
+
+    rows
+}
+
+fn polled_rows(records: &PollingRecord) -> Vec<(String, String)> {
+    records
+        .0
+        .iter()
+        .filter_map(|(address, record)| {
+            Some((address.clone(), serde_json::to_string(record).ok()?))
+        })
+        .collect()
+}
+
+fn write_instances(
+    connection: &Connection,
+    changed: &[(String, String, &str, i64, String)],
+    removed: &[String],
+) -> rusqlite::Result<()> {
+    let mut upsert = connection.prepare_cached(
+        "INSERT INTO instances (address, region, bucket, seq, record)
+         VALUES (?1, ?2, ?3, ?4, ?5)
+         ON CONFLICT (address) DO UPDATE SET
+             region = excluded.region,
+             bucket = excluded.bucket,
+             seq = excluded.seq,
+             record = excluded.record",
+    )?;
+    for (address, region, bucket, seq, record) in changed {
+        upsert.execute(params![address, region, bucket, seq, record])?;
+    }
+
+    let mut delete = connection.prepare_cached("DELETE FROM instances WHERE address = ?1")?;
+    for address in removed {
+        delete.execute([address])?;
+    }
+
+    Ok(())
+}
+
+fn write_polled(
+    connection: &Connection,
+    changed: &[(String, String)],
+    removed: &[String],
+) -> rusqlite::Result<()> {
+    let mut upsert = connection.prepare_cached(
+        "INSERT INTO polling (address, record) VALUES (?1, ?2)
+         ON CONFLICT (address) DO UPDATE SET record = excluded.record",
+    )?;
+    for (address, record) in changed {
+        upsert.execute([address, record])?;
+    }
+
+    let mut delete = connection.prepare_cached("DELETE FROM polling WHERE address = ?1")?;
+    for address in removed {
+        delete.execute([address])?;
+    }
+
+    Ok(())
+}
+
+/// Drops all but the newest `size` polls of `address`.
+fn trim_history(connection: &Connection, address: &str, size: usize) -> rusqlite::Result<()> {
+    let oldest_kept: Option<i64> = connection
+        .query_row(
+            "SELECT id FROM history WHERE address = ?1 ORDER BY id DESC LIMIT 1 OFFSET ?2",
+            params![address, size.saturating_sub(1) as i64],
+            |row| row.get(0),
+        )
+        .optional()?;
+
+    if let Some(id) = oldest_kept {
+        connection.execute(
+            "DELETE FROM history WHERE address = ?1 AND id < ?2",
+            params![address, id],
+        )?;
+    }
+
+    Ok(())
+}
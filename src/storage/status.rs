@@ -0,0 +1,131 @@
+use serde::Serialize;
+
+use crate::*;
+
+/// Number of most recent health changes shown.
+const HEALTH_CHANGES: usize = 20;
+
+/// Everything `/instance/{address}` shows about one instance, with latencies adjusted by the
+/// region's offset.
+///
+/// Backers are never included, and there is no way to list instances from here, the address
+/// has to be known already.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InstanceStatus {
+    pub address: String,
+    pub region: String,
+    /// `up`, `recovering`, `dead` or `pending`, as counted on the home page. Which bucket the
+    /// instance is in is not shown.
+    pub state: &'static str,
+    pub offset: i32,
+    pub last_polled: Option<u64>,
+    pub latest: Option<PolledSingleRecord>,
+    pub history: Option<HistorySummary>,
+    pub health_changes: Vec<HealthChange>,
+}
+
+impl InstanceStatus {
+    /// `None` if the address is not a listed instance, or is blacklisted.
+    pub fn of(address: &str) -> Option<Self> {
+        let address = address::normalise(address).ok()?;
+
+        if is_blacklisted(&address) {
+            return None;
+        }
+
+        let (region, state) = {
+            let records = INSTANCES_RECORD.get().unwrap().lock().unwrap();
+            records.0.iter().find_map(|(region, records)| {
+                records
+                    .bucket(&address)
+                    .map(|bucket| (region.clone(), state(bucket)))
+            })?
+        };
+
+        let offset = OUTBOUND_CONFIG
+            .get()
+            .unwrap()
+            .offsets
+            .0
+            .get(&region)
+            .copied()
+            .unwrap_or_default();
+
+        let latest = POLLING_RECORD
+            .get()
+            .unwrap()
+            .lock()
+            .unwrap()
+            .0
+            .get(&address)
+            .cloned()
+            .map(|mut record| {
+                record.polled_on = None;
+                for outcome in [
+                    &mut record.video,
+                    &mut record.playlist,
+                    &mut record.channel,
+                    &mut record.search,
+                    &mut record.playback,
+                    &mut record.comments,
+                    &mut record.captions,
+                    &mut record.trending,
+                    &mut record.popular,
+                ]
+                .into_iter()
+                .flatten()
+                {
+                    outcome.offset(offset);
+                }
+                record
+            });
+
+        let (history, health_changes) =
+            match POLLING_HISTORY.get().unwrap().lock().unwrap().get(&address) {
+                Some(history) => {
+                    let mut summary = history.summary();
+                    for latency in [
+                        &mut summary.video,
+                        &mut summary.playlist,
+                        &mut summary.channel,
+                        &mut summary.search,
+                        &mut summary.playback,
+                        &mut summary.comments,
+                        &mut summary.captions,
+                        &mut summary.trending,
+                        &mut summary.popular,
+                    ] {
+                        latency.offset(offset);
+                    }
+
+                    let mut changes = history.health_changes();
+                    changes.drain(..changes.len().saturating_sub(HEALTH_CHANGES));
+
+                    (Some(summary), changes)
+                }
+                None => (None, Vec::new()),
+            };
+
+        Some(Self {
+            address,
+            region,
+            state,
+            offset,
+            last_polled: latest.as_ref().map(|record| record.last_polled),
+            latest,
+            history,
+            health_changes,
+        })
+    }
+}
+
+/// Groups buckets the same way as [`RegionRecords::stat`].
+fn state(bucket: &str) -> &'static str {
+    match bucket {
+        "hot" | "recovered" | "stashed" => "up",
+        "recovering" | "stashedRecovering" => "recovering",
+        "dead" | "stashedDead" => "dead",
+        _ => "pending",
+    }
+}
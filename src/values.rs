@@ -3,53 +3,135 @@ use std::{
     sync::{Arc, Mutex, OnceLock},
 };
 
+use log::{error, warn};
+
 use crate::*;
 
 // configs
-pub static MASTER_CONFIG: OnceLock<MasterConfig> = OnceLock::new();
-pub static OUTBOUND_CONFIG: OnceLock<OutboundConfig> = OnceLock::new();
-pub static INTERFACE_CONFIG: OnceLock<InterfaceConfig> = OnceLock::new();
+pub static CONFIGS: Reloadable<Configs> = Reloadable::new();
+pub static MASTER_CONFIG: ConfigView<MasterConfig> =
+    ConfigView::new(|configs: &Configs| &configs.master);
+pub static OUTBOUND_CONFIG: ConfigView<OutboundConfig> =
+    ConfigView::new(|configs: &Configs| &configs.outbound);
+pub static INTERFACE_CONFIG: ConfigView<InterfaceConfig> =
+    ConfigView::new(|configs: &Configs| &configs.interface);
 pub static BLACKLISTS: OnceLock<Arc<Mutex<BlackLists>>> = OnceLock::new();
+pub static ADMIN_CONFIG: OnceLock<Option<AdminConfig>> = OnceLock::new();
+pub static PATHS: OnceLock<Paths> = OnceLock::new();
+pub static GEOIP: ConfigView<GeoIp> = ConfigView::new(|configs: &Configs| &configs.geoip);
 
 // storages
+//
+// Lock order: `INSTANCES_RECORD`, then `POLLING_RECORD`, then `POLLING_HISTORY`. Never lock an
+// earlier one while holding a later one.
+pub static STORAGE: OnceLock<Box<dyn Storage>> = OnceLock::new();
 pub static POLLING_RECORD: OnceLock<Arc<Mutex<PollingRecord>>> = OnceLock::new();
+pub static POLLING_HISTORY: OnceLock<Arc<Mutex<PollingHistory>>> = OnceLock::new();
 pub static INSTANCES_RECORD: OnceLock<Arc<Mutex<InstancesRecords>>> = OnceLock::new();
 pub static SAMPLESETS: OnceLock<Arc<Mutex<Samples>>> = OnceLock::new();
+pub static ARCHIVE: OnceLock<Arc<Mutex<Archive>>> = OnceLock::new();
 
 // generated samples
-pub static VIDEO_ID: OnceLock<Arc<Mutex<String>>> = OnceLock::new();
-pub static PLAYLIST_ID: OnceLock<Arc<Mutex<String>>> = OnceLock::new();
-pub static CHANNEL_ID: OnceLock<Arc<Mutex<String>>> = OnceLock::new();
-pub static SEARCH_TERM: OnceLock<Arc<Mutex<String>>> = OnceLock::new();
+pub static VIDEO_ID: OnceLock<Arc<Mutex<Option<String>>>> = OnceLock::new();
+pub static PLAYLIST_ID: OnceLock<Arc<Mutex<Option<String>>>> = OnceLock::new();
+pub static CHANNEL_ID: OnceLock<Arc<Mutex<Option<String>>>> = OnceLock::new();
+pub static SEARCH_TERM: OnceLock<Arc<Mutex<Option<String>>>> = OnceLock::new();
 
 pub static BLACKLISTED_IP: OnceLock<Arc<Mutex<HashSet<String>>>> = OnceLock::new();
-pub static BLACKLISTED_INSTANCES: OnceLock<Arc<Mutex<HashSet<String>>>> = OnceLock::new();
+pub static BLACKLISTED_INSTANCES: OnceLock<Arc<Mutex<InstanceBlackList>>> = OnceLock::new();
 
 #[allow(clippy::type_complexity)]
 pub static INSTANCES_STATS: OnceLock<Arc<Mutex<(u32, u32, u32, u32)>>> = OnceLock::new();
 pub static CONCURRENT_POLLS: OnceLock<Arc<Mutex<u32>>> = OnceLock::new();
 pub static POLL_QUEUE: OnceLock<Arc<Mutex<Vec<String>>>> = OnceLock::new();
 
+pub static SUBMISSIONS_PER_IP: OnceLock<Arc<Mutex<RateLimiter>>> = OnceLock::new();
+pub static SUBMISSIONS_GLOBAL: OnceLock<Arc<Mutex<RateLimiter>>> = OnceLock::new();
+
+pub static ENUMERATION: OnceLock<Arc<Mutex<EnumerationTracker>>> = OnceLock::new();
+pub static BANS: OnceLock<Arc<Mutex<Bans>>> = OnceLock::new();
+
+pub static METRICS: OnceLock<Arc<Mutex<Metrics>>> = OnceLock::new();
+
 pub async fn init() {
-    let _ = MASTER_CONFIG.set(MasterConfig::load().await.unwrap());
-    let _ = OUTBOUND_CONFIG.set(OutboundConfig::load().await.unwrap());
-    let _ = INTERFACE_CONFIG.set(InterfaceConfig::load().await.unwrap());
-    let _ = BLACKLISTS.set(Arc::new(Mutex::new(BlackLists::load().await.unwrap())));
-
-    let _ = POLLING_RECORD.set(Arc::new(Mutex::new(PollingRecord::load().await.unwrap())));
-    let _ = INSTANCES_RECORD.set(Arc::new(Mutex::new(
-        InstancesRecords::load().await.unwrap(),
+    let master = MasterConfig::load_or_create().await.unwrap();
+    let outbound = OutboundConfig::load_or_create().await.unwrap();
+    let interface = InterfaceConfig::load_or_create().await.unwrap();
+    if let Err(e) = validate(&master, &outbound, &interface) {
+        error!("{e}");
+        std::process::exit(1);
+    }
+    // as on reload, a database that is set but cannot be opened is an error
+    let geoip = GeoIp::open(&master.geoip).unwrap_or_else(|e| {
+        error!("{}: geoip: {e}", MasterConfig::path().display());
+        std::process::exit(1);
+    });
+    CONFIGS.set(Configs {
+        master: Arc::new(master),
+        outbound: Arc::new(outbound),
+        interface: Arc::new(interface),
+        geoip: Arc::new(geoip),
+    });
+    let _ = BLACKLISTS.set(Arc::new(Mutex::new(
+        BlackLists::load_or_create().await.unwrap(),
     )));
-    let _ = SAMPLESETS.set(Arc::new(Mutex::new(Samples::load().await.unwrap())));
+    let _ = ADMIN_CONFIG.set(AdminConfig::load_or_create().await.ok());
 
-    BLACKLISTS.get().unwrap().lock().unwrap().init();
+    let storage = MASTER_CONFIG
+        .get()
+        .unwrap()
+        .storage
+        .open()
+        .await
+        .unwrap_or_else(|e| {
+            error!("Cannot open storage: {e}");
+            std::process::exit(1);
+        });
+    let (instances, polled, history) = storage.load().await.unwrap();
+    let _ = STORAGE.set(storage);
+    let _ = POLLING_RECORD.set(Arc::new(Mutex::new(polled)));
+    let _ = POLLING_HISTORY.set(Arc::new(Mutex::new(history)));
+    let _ = INSTANCES_RECORD.set(Arc::new(Mutex::new(instances)));
+    let _ = SAMPLESETS.set(Arc::new(Mutex::new(
+        Samples::load_or_create().await.unwrap(),
+    )));
+    let _ = ARCHIVE.set(Arc::new(Mutex::new(
+        Archive::load_or_create().await.unwrap(),
+    )));
+
+    BLACKLISTS.get().unwrap().lock().unwrap().apply();
     SAMPLESETS.get().unwrap().lock().unwrap().init();
+
     INSTANCES_STATS
         .set(Arc::new(Mutex::new(
             INSTANCES_RECORD.get().unwrap().lock().unwrap().stat(),
         )))
         .unwrap();
+    let blacklisted = INSTANCES_RECORD
+        .get()
+        .unwrap()
+        .lock()
+        .unwrap()
+        .blacklisted();
+    if remove_instances(&blacklisted).await {
+        for instance in blacklisted {
+            warn!("Removed blacklisted instance {instance}");
+        }
+    }
 
     CONCURRENT_POLLS.set(Arc::new(Mutex::new(0))).unwrap();
     POLL_QUEUE.set(Arc::new(Mutex::new(Vec::new()))).unwrap();
+    let _ = METRICS.set(Arc::new(Mutex::new(Metrics::default())));
+
+    let master = MASTER_CONFIG.get().unwrap();
+    let _ = SUBMISSIONS_PER_IP.set(Arc::new(Mutex::new(RateLimiter::new(
+        master.submissions.per_ip,
+    ))));
+    let _ = SUBMISSIONS_GLOBAL.set(Arc::new(Mutex::new(RateLimiter::new(
+        master.submissions.global,
+    ))));
+    let _ = ENUMERATION.set(Arc::new(Mutex::new(EnumerationTracker::new(
+        master.enumeration,
+    ))));
+    let _ = BANS.set(Arc::new(Mutex::new(Bans::load_or_create().await.unwrap())));
 }